@@ -0,0 +1,49 @@
+use fanotify::fdinfo::{scan_groups, KernelMarkTarget};
+
+fn main() {
+    let groups = match scan_groups() {
+        Ok(groups) => groups,
+        Err(err) => {
+            eprintln!("failed to scan /proc: {}", err);
+            std::process::exit(1);
+        }
+    };
+    for group in groups {
+        let exe = group
+            .exe
+            .as_ref()
+            .map_or("?".to_string(), |exe| exe.display().to_string());
+        println!(
+            "pid {} ({}) fd {}: class {:?} flags {:#x} event-flags {:#x}",
+            group.pid,
+            exe,
+            group.fd,
+            group.state.class(),
+            group.state.flags,
+            group.state.event_flags
+        );
+        for mark in &group.state.marks {
+            let target = match &mark.target {
+                KernelMarkTarget::Inode { ino, .. } => {
+                    let dev = mark.dev().unwrap_or_default();
+                    format!(
+                        "inode {}:{} ino {}",
+                        libc::major(dev),
+                        libc::minor(dev),
+                        ino
+                    )
+                }
+                KernelMarkTarget::Mount { mnt_id } => format!("mount {}", mnt_id),
+                KernelMarkTarget::Filesystem { .. } => {
+                    let dev = mark.dev().unwrap_or_default();
+                    format!("filesystem {}:{}", libc::major(dev), libc::minor(dev))
+                }
+                KernelMarkTarget::MountNamespace { ns } => format!("mount namespace {}", ns),
+            };
+            println!(
+                "    {} mask {:#x} ignored_mask {:#x} mflags {:#x}",
+                target, mark.mask, mark.ignored_mask, mark.mflags
+            );
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Declarative description of a group and its marks, loadable from TOML.
+//!
+//! ```toml
+//! [group]
+//! class = "content"
+//! report_fid = false
+//!
+//! [[mark]]
+//! kind = "mount"
+//! path = "/srv"
+//! events = ["open_perm", "close_write"]
+//!
+//! [[mark]]
+//! kind = "inode"
+//! path = "/srv/cache"
+//! ignore = ["open_perm"]
+//! ignore_options = { survive_modify = true, ondir = true }
+//! ```
+//!
+//! The group settings are applied once with `GroupConfig::to_builder`. The marks can be
+//! applied again every time the file changes with `Fanotify::apply_config`, which only adds
+//! and removes the events that differ from the marks the group already has.
+
+use crate::high_level::{
+    EventAccess, FanEvent, Fanotify, FanotifyBuilder, FanotifyMode, IgnoreOptions,
+};
+use crate::low_level::AT_FDCWD;
+use crate::mark_set::{MarkOp, MarkSet, MarkSetError};
+use crate::registry::{Mark, MarkKind, MarkTarget};
+use serde::{Deserialize, Serialize};
+use std::fs::read_to_string;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// The layout of a group: how it is initialized and what it marks.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatchConfig {
+    pub group: GroupConfig,
+    #[serde(rename = "mark")]
+    pub marks: Vec<MarkConfig>,
+}
+
+/// The settings `fanotify_init` is called with. Each flag maps to the `FanotifyBuilder` method
+/// of the same name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GroupConfig {
+    pub class: FanotifyMode,
+    pub nonblocking: bool,
+    pub report_fid: bool,
+    pub report_dir_fid: bool,
+    pub report_name: bool,
+    pub report_tid: bool,
+    pub report_pidfd: bool,
+    pub report_mnt: bool,
+    pub unlimited_queue: bool,
+    pub unlimited_marks: bool,
+    pub enable_audit: bool,
+    pub event_access: EventAccess,
+    pub large_file: bool,
+    pub no_atime: bool,
+    pub process_info: bool,
+    pub process_ancestry: bool,
+    pub stat_on_read: bool,
+}
+
+/// A mark of the group.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MarkConfig {
+    pub kind: MarkKind,
+    pub path: PathBuf,
+    /// Events the mark listens for.
+    #[serde(default)]
+    pub events: Vec<FanEvent>,
+    /// Events the mark ignores.
+    #[serde(default)]
+    pub ignore: Vec<FanEvent>,
+    #[serde(default)]
+    pub ignore_options: IgnoreOptions,
+}
+
+impl WatchConfig {
+    pub fn from_toml(contents: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    /// Reads and parses the TOML file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_toml(&read_to_string(path)?).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(self)
+    }
+}
+
+impl Default for GroupConfig {
+    fn default() -> Self {
+        Self {
+            class: FanotifyMode::NOTIF,
+            nonblocking: false,
+            report_fid: false,
+            report_dir_fid: false,
+            report_name: false,
+            report_tid: false,
+            report_pidfd: false,
+            report_mnt: false,
+            unlimited_queue: false,
+            unlimited_marks: false,
+            enable_audit: false,
+            event_access: EventAccess::ReadOnly,
+            large_file: false,
+            no_atime: false,
+            process_info: false,
+            process_ancestry: false,
+            stat_on_read: false,
+        }
+    }
+}
+
+impl GroupConfig {
+    pub fn to_builder(&self) -> FanotifyBuilder {
+        let mut builder = FanotifyBuilder::new()
+            .with_class(self.class)
+            .with_event_access(self.event_access);
+        for (set, flag) in [
+            (self.nonblocking, FanotifyBuilder::nonblocking as fn(_) -> _),
+            (self.report_fid, FanotifyBuilder::report_fid),
+            (self.report_dir_fid, FanotifyBuilder::report_dir_fid),
+            (self.report_name, FanotifyBuilder::report_name),
+            (self.report_tid, FanotifyBuilder::report_tid),
+            (self.report_pidfd, FanotifyBuilder::report_pidfd),
+            (self.report_mnt, FanotifyBuilder::report_mnt),
+            (self.unlimited_queue, FanotifyBuilder::unlimited_queue),
+            (self.unlimited_marks, FanotifyBuilder::unlimited_marks),
+            (self.enable_audit, FanotifyBuilder::enable_audit),
+            (self.large_file, FanotifyBuilder::large_file),
+            (self.no_atime, FanotifyBuilder::no_atime),
+            (self.process_info, FanotifyBuilder::process_info),
+            (self.process_ancestry, FanotifyBuilder::process_ancestry),
+            (self.stat_on_read, FanotifyBuilder::stat_on_read),
+        ] {
+            if set {
+                builder = flag(builder);
+            }
+        }
+        builder
+    }
+}
+
+impl MarkConfig {
+    fn mask(&self) -> u64 {
+        self.events
+            .iter()
+            .fold(0, |mask, event| mask | u64::from(*event))
+    }
+
+    fn ignored_mask(&self) -> u64 {
+        let mask = self
+            .ignore
+            .iter()
+            .fold(0, |mask, event| mask | u64::from(*event));
+        if mask == 0 {
+            0
+        } else {
+            self.ignore_options.to_mask(mask)
+        }
+    }
+}
+
+/// A mark the configuration asks for, with the marks of the same object merged.
+struct Wanted<'a> {
+    config: &'a MarkConfig,
+    target: Option<MarkTarget>,
+    mask: u64,
+    ignored_mask: u64,
+}
+
+impl Wanted<'_> {
+    fn is(&self, mark: &Mark) -> bool {
+        self.config.kind == mark.kind
+            && self
+                .target
+                .is_some_and(|target| target.same_mark(mark.kind, &mark.target))
+    }
+}
+
+impl Fanotify {
+    /// Brings the marks of the group in line with `config.marks`, changing only what differs
+    /// from the marks recorded in its registry (see `marks`). Marks the configuration no longer
+    /// lists are removed, and those whose ignore options change are removed and added back.
+    /// `config.group` is ignored, as a group can't be reinitialized.
+    ///
+    /// The changes are applied as a `MarkSet`, so on failure the group keeps the marks it had.
+    /// On success the applied operations are returned, which are empty if nothing changed.
+    pub fn apply_config(&self, config: &WatchConfig) -> Result<MarkSet, MarkSetError> {
+        let mut wanted: Vec<Wanted> = Vec::new();
+        for mark in &config.marks {
+            let target = MarkTarget::resolve(AT_FDCWD, Some(mark.path.as_os_str()), true).ok();
+            let existing = wanted.iter_mut().find(|wanted| {
+                wanted.config.kind == mark.kind
+                    && wanted
+                        .target
+                        .zip(target)
+                        .is_some_and(|(a, b)| a.same_mark(mark.kind, &b))
+            });
+            match existing {
+                Some(wanted) => {
+                    wanted.mask |= mark.mask();
+                    wanted.ignored_mask |= mark.ignored_mask();
+                }
+                None => wanted.push(Wanted {
+                    config: mark,
+                    target,
+                    mask: mark.mask(),
+                    ignored_mask: mark.ignored_mask(),
+                }),
+            }
+        }
+
+        let mut removals = MarkSet::new();
+        let mut additions = MarkSet::new();
+        for mark in self.marks() {
+            let Some(path) = mark.path.clone() else {
+                continue;
+            };
+            let (mask, ignored_mask, options) = match wanted.iter().find(|wanted| wanted.is(&mark))
+            {
+                Some(wanted) => (
+                    wanted.mask,
+                    wanted.ignored_mask,
+                    wanted.config.ignore_options,
+                ),
+                None => (0, 0, mark.ignore_options),
+            };
+            let kind = mark.kind;
+            // The kernel keeps the ignore flags of a mark until it's gone, so a change of
+            // options removes the whole mark and adds it back.
+            let replace = ignored_mask != 0 && options != mark.ignore_options;
+            let (kept_mask, kept_ignored) = if replace {
+                (0, 0)
+            } else {
+                (mark.mask & mask, mark.ignored_mask & ignored_mask)
+            };
+            if mark.mask & !kept_mask != 0 {
+                removals = removals.with_op(MarkOp::Remove {
+                    kind,
+                    mask: mark.mask & !kept_mask,
+                    path: path.clone(),
+                });
+            }
+            if mark.ignored_mask & !kept_ignored != 0 {
+                removals = removals.with_op(MarkOp::Unignore {
+                    kind,
+                    mask: mark.ignored_mask & !kept_ignored,
+                    path: path.clone(),
+                });
+            }
+            if mask & !kept_mask != 0 {
+                additions = additions.with_op(MarkOp::Add {
+                    kind,
+                    mask: mask & !kept_mask,
+                    path: path.clone(),
+                });
+            }
+            if ignored_mask & !kept_ignored != 0 {
+                additions = additions.with_op(MarkOp::Ignore {
+                    kind,
+                    mask: ignored_mask & !kept_ignored,
+                    path,
+                    options,
+                });
+            }
+        }
+
+        let marks = self.marks();
+        for wanted in &wanted {
+            if marks.iter().any(|mark| wanted.is(mark)) {
+                continue;
+            }
+            let (kind, path) = (wanted.config.kind, wanted.config.path.clone());
+            if wanted.mask != 0 {
+                additions = additions.with_op(MarkOp::Add {
+                    kind,
+                    mask: wanted.mask,
+                    path: path.clone(),
+                });
+            }
+            if wanted.ignored_mask != 0 {
+                additions = additions.with_op(MarkOp::Ignore {
+                    kind,
+                    mask: wanted.ignored_mask,
+                    path,
+                    options: wanted.config.ignore_options,
+                });
+            }
+        }
+
+        // Removals go first, so that a reload doesn't need room for both layouts in a group
+        // limited to 8192 marks.
+        let changes = additions
+            .ops()
+            .iter()
+            .cloned()
+            .fold(removals, MarkSet::with_op);
+        changes.apply(self)?;
+        Ok(changes)
+    }
+}
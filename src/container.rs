@@ -0,0 +1,108 @@
+//! Attribution of processes to containers, from the cgroup paths container runtimes create.
+
+use std::fmt;
+
+/// The runtime that created a container, as told by the naming of its cgroup.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ContainerRuntime {
+    /// `cri-containerd-<id>.scope`, or `<id>` below `kubepods` with the cgroupfs driver.
+    Containerd,
+    /// `crio-<id>.scope`, or `crio-<id>` with the cgroupfs driver.
+    CriO,
+    /// `docker-<id>.scope`, or `<id>` below `docker` with the cgroupfs driver.
+    Docker,
+    /// `libpod-<id>.scope`, or `libpod-<id>` with the cgroupfs driver.
+    Podman,
+    /// A bare container id below a parent no runtime is known for.
+    Unknown,
+}
+
+/// A container found in a cgroup path.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ContainerId {
+    pub runtime: ContainerRuntime,
+    /// The full id of the container, 64 hexadecimal digits.
+    pub id: String,
+}
+
+impl fmt::Display for ContainerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.id)
+    }
+}
+
+/// The cgroup v2 path in the contents of `/proc/<pid>/cgroup`, i.e. the path of its `0::`
+/// line. `None` on systems using only cgroup v1.
+pub fn unified_path(contents: &str) -> Option<&str> {
+    contents.lines().find_map(|line| line.strip_prefix("0::"))
+}
+
+/// The container a process belongs to, from the contents of its `/proc/<pid>/cgroup`. The
+/// cgroup v2 path is looked at first, then the paths of the cgroup v1 hierarchies.
+pub fn from_cgroup(contents: &str) -> Option<ContainerId> {
+    unified_path(contents).and_then(container_id).or_else(|| {
+        contents
+            .lines()
+            .filter(|line| !line.starts_with("0::"))
+            .filter_map(|line| line.splitn(3, ':').nth(2))
+            .find_map(container_id)
+    })
+}
+
+/// The container of the cgroup `path`, following the conventions of containerd, CRI-O,
+/// Docker and Podman. The innermost container wins, as processes of nested containers are
+/// below the cgroup of their parent.
+///
+/// ```
+/// use fanotify::container::{container_id, ContainerRuntime};
+/// let id = "3f1b2c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f708192a3b4c5d6e7f809";
+/// let path = format!(
+///     "/kubepods.slice/kubepods-besteffort.slice/\
+///      kubepods-besteffort-pod1234.slice/cri-containerd-{}.scope",
+///     id
+/// );
+/// let container = container_id(&path).unwrap();
+/// assert_eq!(container.runtime, ContainerRuntime::Containerd);
+/// assert_eq!(container.id, id);
+/// assert!(container_id("/user.slice/user-1000.slice/session-2.scope").is_none());
+/// ```
+pub fn container_id(path: &str) -> Option<ContainerId> {
+    let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+    components
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(i, component)| {
+            let name = component.strip_suffix(".scope").unwrap_or(component);
+            let (runtime, id) = [
+                ("cri-containerd-", ContainerRuntime::Containerd),
+                ("crio-", ContainerRuntime::CriO),
+                ("docker-", ContainerRuntime::Docker),
+                ("libpod-", ContainerRuntime::Podman),
+            ]
+            .into_iter()
+            .find_map(|(prefix, runtime)| Some((runtime, name.strip_prefix(prefix)?)))
+            .unwrap_or_else(|| (bare_runtime(&components[..i]), name));
+            // The monitors of CRI-O and Podman (`crio-conmon-<id>`, `libpod-conmon-<id>`)
+            // don't match, as their name isn't followed by the id alone.
+            is_container_id(id).then(|| ContainerId {
+                runtime,
+                id: id.to_string(),
+            })
+        })
+}
+
+/// The runtime of a container whose cgroup is named after its id alone, from its parents.
+fn bare_runtime(parents: &[&str]) -> ContainerRuntime {
+    if parents.contains(&"docker") {
+        ContainerRuntime::Docker
+    } else if parents.iter().any(|parent| parent.starts_with("kubepods")) {
+        ContainerRuntime::Containerd
+    } else {
+        ContainerRuntime::Unknown
+    }
+}
+
+fn is_container_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
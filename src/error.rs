@@ -0,0 +1,182 @@
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+
+/// The fanotify call that failed, together with the arguments it was given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Call {
+    /// `fanotify_init(flags, event_f_flags)`
+    Init { flags: u32, event_f_flags: u32 },
+    /// `fanotify_mark(fd, flags, mask, dirfd, path)`
+    Mark {
+        flags: u32,
+        mask: u64,
+        path: Option<PathBuf>,
+    },
+}
+
+impl fmt::Display for Call {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Call::Init {
+                flags,
+                event_f_flags,
+            } => write!(
+                f,
+                "fanotify_init(flags={:#x}, event_f_flags={:#x})",
+                flags, event_f_flags
+            ),
+            Call::Mark {
+                flags,
+                mask,
+                path: Some(path),
+            } => write!(
+                f,
+                "fanotify_mark(flags={:#x}, mask={:#x}, path={:?})",
+                flags, mask, path
+            ),
+            Call::Mark {
+                flags,
+                mask,
+                path: None,
+            } => write!(f, "fanotify_mark(flags={:#x}, mask={:#x})", flags, mask),
+        }
+    }
+}
+
+/// An error returned by a fanotify call, with the errno translated into its likely cause.
+///
+/// Every variant keeps the `Call` that failed. The ones produced by the kernel also keep the
+/// original `std::io::Error`, which is available through `std::error::Error::source`.
+#[derive(Debug)]
+pub enum FanotifyError {
+    /// `EPERM`: the caller lacks `CAP_SYS_ADMIN`, which `fanotify_init`, mount and filesystem
+    /// marks, and most init flags require.
+    MissingCapability { call: Call, source: Error },
+    /// `EMFILE` from `fanotify_init`: the per-user limit of 128 fanotify groups was reached,
+    /// or the process is out of file descriptors.
+    TooManyGroups { call: Call, source: Error },
+    /// `ENOSPC` from `fanotify_mark`: the group reached its limit of 8192 marks.
+    /// See `FAN_UNLIMITED_MARKS`.
+    TooManyMarks { call: Call, source: Error },
+    /// `EINVAL`: a flag is not supported by the running kernel, or the mask is not valid for
+    /// this kind of mark (e.g. directory entry events on a mount mark).
+    Unsupported { call: Call, source: Error },
+    /// `EXDEV` from `fanotify_mark`: the object lives on a filesystem (or subvolume) whose fsid
+    /// differs from the one of its superblock, so it can't be marked by a group reporting file ids.
+    CrossDevice { call: Call, source: Error },
+    /// Any other error reported by the kernel.
+    Os { call: Call, source: Error },
+    /// The call was rejected before reaching the kernel because its flags or mask form a
+    /// combination the kernel is documented to refuse.
+    InvalidCombination { call: Call, reason: &'static str },
+    /// The mark can't be changed again by `Fanotify::remove_mark` or `Fanotify::restore`, as
+    /// the registry has neither a path nor a file descriptor leading to its object.
+    UnreachableMark { call: Call },
+}
+
+impl FanotifyError {
+    /// Classifies the error returned by the kernel for `call`.
+    pub fn from_os(call: Call, source: Error) -> Self {
+        let is_init = matches!(call, Call::Init { .. });
+        match source.raw_os_error() {
+            Some(libc::EPERM) => FanotifyError::MissingCapability { call, source },
+            Some(libc::EMFILE) if is_init => FanotifyError::TooManyGroups { call, source },
+            Some(libc::ENOSPC) if !is_init => FanotifyError::TooManyMarks { call, source },
+            Some(libc::EINVAL) => FanotifyError::Unsupported { call, source },
+            Some(libc::EXDEV) if !is_init => FanotifyError::CrossDevice { call, source },
+            _ => FanotifyError::Os { call, source },
+        }
+    }
+
+    /// The call that failed.
+    pub fn call(&self) -> &Call {
+        match self {
+            FanotifyError::MissingCapability { call, .. }
+            | FanotifyError::TooManyGroups { call, .. }
+            | FanotifyError::TooManyMarks { call, .. }
+            | FanotifyError::Unsupported { call, .. }
+            | FanotifyError::CrossDevice { call, .. }
+            | FanotifyError::Os { call, .. }
+            | FanotifyError::InvalidCombination { call, .. }
+            | FanotifyError::UnreachableMark { call } => call,
+        }
+    }
+
+    /// The errno reported by the kernel, if any.
+    pub fn raw_os_error(&self) -> Option<i32> {
+        self.io_error().and_then(Error::raw_os_error)
+    }
+
+    fn io_error(&self) -> Option<&Error> {
+        match self {
+            FanotifyError::MissingCapability { source, .. }
+            | FanotifyError::TooManyGroups { source, .. }
+            | FanotifyError::TooManyMarks { source, .. }
+            | FanotifyError::Unsupported { source, .. }
+            | FanotifyError::CrossDevice { source, .. }
+            | FanotifyError::Os { source, .. } => Some(source),
+            FanotifyError::InvalidCombination { .. } | FanotifyError::UnreachableMark { .. } => {
+                None
+            }
+        }
+    }
+}
+
+impl fmt::Display for FanotifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cause = match self {
+            FanotifyError::MissingCapability { .. } => {
+                "operation not permitted, CAP_SYS_ADMIN is required"
+            }
+            FanotifyError::TooManyGroups { .. } => {
+                "too many fanotify groups, the limit is 128 per user"
+            }
+            FanotifyError::TooManyMarks { .. } => {
+                "too many marks for this group, consider FAN_UNLIMITED_MARKS"
+            }
+            FanotifyError::Unsupported { call: Call::Init { .. }, .. } => {
+                "a flag is not supported by this kernel"
+            }
+            FanotifyError::Unsupported { .. } => {
+                "a flag is not supported by this kernel, or the mask is not valid for this mark type"
+            }
+            FanotifyError::CrossDevice { .. } => {
+                "the object has a different fsid than its filesystem and can't be marked with file ids"
+            }
+            FanotifyError::Os { source, .. } => {
+                return write!(f, "{} failed: {}", self.call(), source)
+            }
+            FanotifyError::InvalidCombination { reason, .. } => {
+                return write!(f, "{} rejected: {}", self.call(), reason)
+            }
+            FanotifyError::UnreachableMark { .. } => {
+                return write!(f, "{} skipped: the object of the mark has no known path", self.call())
+            }
+        };
+        write!(f, "{} failed: {}", self.call(), cause)
+    }
+}
+
+impl std::error::Error for FanotifyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.io_error().map(|e| e as _)
+    }
+}
+
+/// Errors reported by the kernel convert back into the original `std::io::Error`, so that
+/// `raw_os_error` still gives the errno.
+impl From<FanotifyError> for Error {
+    fn from(err: FanotifyError) -> Error {
+        match err {
+            FanotifyError::MissingCapability { source, .. }
+            | FanotifyError::TooManyGroups { source, .. }
+            | FanotifyError::TooManyMarks { source, .. }
+            | FanotifyError::Unsupported { source, .. }
+            | FanotifyError::CrossDevice { source, .. }
+            | FanotifyError::Os { source, .. } => source,
+            FanotifyError::InvalidCombination { .. } => Error::new(ErrorKind::InvalidInput, err),
+            FanotifyError::UnreachableMark { .. } => Error::new(ErrorKind::NotFound, err),
+        }
+    }
+}
@@ -0,0 +1,297 @@
+//! An audit trail of the programs run on a system, built from `FAN_OPEN_EXEC` and
+//! `FAN_OPEN_EXEC_PERM` events.
+//!
+//! ```no_run
+//! use fanotify::exec::ExecTracker;
+//! use fanotify::high_level::*;
+//! let ft = FanotifyBuilder::new().register().unwrap();
+//! ft.add_mountpoint(FAN_OPEN_EXEC | FAN_OPEN, "/").unwrap();
+//! let mut tracker = ExecTracker::new();
+//! loop {
+//!     for event in ft.read_event() {
+//!         if let Some(record) = tracker.track(&event) {
+//!             println!("{} ran {}", record.pid, record.path.display());
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::high_level::{fstat, Event, FanEvent};
+use crate::process::{read_cmdline, ProcessInfo};
+use std::collections::HashMap;
+use std::fs::{metadata, read_link};
+use std::io::Error;
+use std::os::fd::BorrowedFd;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// The program header naming the ELF interpreter.
+const PT_INTERP: u64 = 3;
+
+/// Programs that run the script named on their command line, matched against the file name
+/// of the executable with an optional version suffix (e.g. `python3.12`).
+pub const INTERPRETERS: &[&str] = &[
+    "sh", "bash", "dash", "zsh", "ksh", "mksh", "fish", "python", "perl", "ruby", "node", "php",
+    "lua", "tclsh", "awk", "gawk", "Rscript",
+];
+
+/// How a file came to be run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecKind {
+    /// The file was executed. For `#!` scripts, the kernel reports the script and then its
+    /// interpreter. The ELF interpreter of dynamically linked programs (`ld-linux.so`) is
+    /// opened for exec too, but isn't recorded, see `ExecTracker`.
+    Exec,
+    /// The file was opened by a freshly executed `interpreter` and is named on its command
+    /// line, e.g. `python3 script.py`.
+    Script { interpreter: PathBuf },
+}
+
+/// A program (or script) run on the system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecRecord {
+    pub kind: ExecKind,
+    pub path: PathBuf,
+    pub dev: u64,
+    pub ino: u64,
+    /// The digest computed by the hasher of the tracker, if it has one and it succeeded.
+    pub hash: Option<Vec<u8>>,
+    pub pid: i32,
+    /// The parent of the process, `None` if the process couldn't be read.
+    pub ppid: Option<i32>,
+    /// The real uid of the process, `None` if the process couldn't be read.
+    pub uid: Option<u32>,
+    /// When the event was tracked. fanotify doesn't timestamp events, so this lags behind
+    /// the exec by the time the event spent in the queue.
+    pub time: SystemTime,
+}
+
+type Hasher = Box<dyn FnMut(BorrowedFd<'_>) -> Result<Vec<u8>, Error> + Send>;
+
+/// An interpreter executed by a process, waiting for the script it opens.
+struct PendingScript {
+    interpreter: PathBuf,
+    since: Instant,
+}
+
+/// The ELF interpreter of a program executed by a process, expected to be opened next.
+struct PendingLoader {
+    dev: u64,
+    ino: u64,
+    since: Instant,
+}
+
+/// Turns exec events into `ExecRecord`s.
+///
+/// Besides `FAN_OPEN_EXEC` (or `FAN_OPEN_EXEC_PERM`), mark `FAN_OPEN` (or `FAN_OPEN_PERM`)
+/// to catch scripts given to an interpreter: after an interpreter of `INTERPRETERS` is
+/// executed, the first file its process opens that is named on its command line is recorded
+/// as a `ExecKind::Script`. The command line is read when the open is tracked, so scripts of
+/// processes that already exited are missed; permission events don't have this issue.
+///
+/// The kernel also reports an exec of the ELF interpreter (`ld-linux.so`) after each
+/// dynamically linked program. It is recognized from the `PT_INTERP` header of the program
+/// and skipped. `/proc/<pid>/exe` can't tell them apart, as events may be read before the
+/// exec completes.
+///
+/// Only events carrying a file descriptor are tracked, which rules out groups reporting file
+/// ids.
+pub struct ExecTracker {
+    hasher: Option<Hasher>,
+    interpreters: Vec<String>,
+    script_timeout: Duration,
+    pending: HashMap<i32, PendingScript>,
+    /// The ELF interpreters of the programs just executed, by pid.
+    loaders: HashMap<i32, PendingLoader>,
+}
+
+impl ExecTracker {
+    pub fn new() -> Self {
+        Self {
+            hasher: None,
+            interpreters: INTERPRETERS.iter().map(|name| name.to_string()).collect(),
+            script_timeout: Duration::from_secs(5),
+            pending: HashMap::new(),
+            loaders: HashMap::new(),
+        }
+    }
+
+    /// Computes `ExecRecord::hash` from the file descriptor of each executed file. The
+    /// descriptor is shared with the event, so the hasher should read it with `pread` rather
+    /// than move its offset.
+    pub fn with_hasher<F>(self, hasher: F) -> Self
+    where
+        F: FnMut(BorrowedFd<'_>) -> Result<Vec<u8>, Error> + Send + 'static,
+    {
+        Self {
+            hasher: Some(Box::new(hasher)),
+            ..self
+        }
+    }
+
+    /// Also treats the programs named `name` as interpreters.
+    pub fn with_interpreter(mut self, name: &str) -> Self {
+        self.interpreters.push(name.to_string());
+        self
+    }
+
+    /// How long after an interpreter is executed the script it opens is looked for.
+    pub fn with_script_timeout(self, timeout: Duration) -> Self {
+        Self {
+            script_timeout: timeout,
+            ..self
+        }
+    }
+
+    /// The record of `event`, if it is the exec of a file or the open of a script by an
+    /// interpreter.
+    pub fn track(&mut self, event: &Event) -> Option<ExecRecord> {
+        let timeout = self.script_timeout;
+        self.pending
+            .retain(|_, pending| pending.since.elapsed() < timeout);
+        self.loaders
+            .retain(|_, loader| loader.since.elapsed() < timeout);
+        if event.fd < 0 {
+            return None;
+        }
+        let is_exec = event
+            .events
+            .iter()
+            .any(|event| matches!(event, FanEvent::OpenExec | FanEvent::OpenExecPerm));
+        let kind = if is_exec {
+            if let Some(loader) = self.loaders.remove(&event.pid) {
+                if dev_ino(event.fd) == Some((loader.dev, loader.ino)) {
+                    return None;
+                }
+            }
+            if let Some((dev, ino)) = elf_interpreter(event) {
+                let since = Instant::now();
+                self.loaders
+                    .insert(event.pid, PendingLoader { dev, ino, since });
+            }
+            let path = Path::new(&event.path);
+            if self.is_interpreter(path) {
+                self.pending.insert(
+                    event.pid,
+                    PendingScript {
+                        interpreter: path.to_path_buf(),
+                        since: Instant::now(),
+                    },
+                );
+            } else {
+                self.pending.remove(&event.pid);
+            }
+            ExecKind::Exec
+        } else {
+            if !self.pending.contains_key(&event.pid) || !is_script_of(event) {
+                return None;
+            }
+            let pending = self.pending.remove(&event.pid)?;
+            ExecKind::Script {
+                interpreter: pending.interpreter,
+            }
+        };
+        let (dev, ino) = dev_ino(event.fd)?;
+        let process = match &event.process {
+            Some(process) => Some(process.clone()),
+            None => ProcessInfo::read(event.pid).ok(),
+        };
+        let fd = unsafe { BorrowedFd::borrow_raw(event.fd) };
+        Some(ExecRecord {
+            kind,
+            path: PathBuf::from(&event.path),
+            dev,
+            ino,
+            hash: self.hasher.as_mut().and_then(|hasher| hasher(fd).ok()),
+            pid: event.pid,
+            ppid: process.as_ref().map(|process| process.ppid),
+            uid: process.as_ref().map(|process| process.uid),
+            time: SystemTime::now(),
+        })
+    }
+
+    fn is_interpreter(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        self.interpreters.iter().any(|interpreter| {
+            name.strip_prefix(interpreter.as_str())
+                .is_some_and(|version| version.chars().all(|c| c.is_ascii_digit() || c == '.'))
+        })
+    }
+}
+
+impl Default for ExecTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether the file of `event` is named by an argument of its process. Relative arguments
+/// are resolved against the working directory of the process.
+fn is_script_of(event: &Event) -> bool {
+    let Some((dev, ino)) = dev_ino(event.fd) else {
+        return false;
+    };
+    let Ok(cmdline) = read_cmdline(event.pid) else {
+        return false;
+    };
+    let cwd = read_link(format!("/proc/{}/cwd", event.pid)).ok();
+    cmdline
+        .iter()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .filter_map(|arg| match &cwd {
+            Some(cwd) => Some(cwd.join(arg)),
+            None => Some(PathBuf::from(arg)).filter(|arg| arg.is_absolute()),
+        })
+        .any(|arg| metadata(arg).is_ok_and(|meta| meta.dev() == dev && meta.ino() == ino))
+}
+
+/// The device and inode of the ELF interpreter named by the `PT_INTERP` header of the file of
+/// `event`, resolved in the root directory of its process. `None` for statically linked
+/// programs and files that aren't ELF.
+pub(crate) fn elf_interpreter(event: &Event) -> Option<(u64, u64)> {
+    let header = event.read_prefix(64).ok()?;
+    if header.len() < 64 || header[..4] != *b"\x7fELF" {
+        return None;
+    }
+    let is_64 = header[4] == 2;
+    let big_endian = header[5] == 2;
+    let field = |bytes: &[u8], (at, len): (usize, usize)| -> Option<u64> {
+        let mut value = [0; 8];
+        let bytes = bytes.get(at..at + len)?;
+        if big_endian {
+            value[8 - len..].copy_from_slice(bytes);
+            Some(u64::from_be_bytes(value))
+        } else {
+            value[..len].copy_from_slice(bytes);
+            Some(u64::from_le_bytes(value))
+        }
+    };
+    // The offsets of e_phoff, e_phentsize, e_phnum, p_offset and p_filesz.
+    let [phoff, phentsize, phnum, offset, filesz] = if is_64 {
+        [(32, 8), (54, 2), (56, 2), (8, 8), (32, 8)]
+    } else {
+        [(28, 4), (42, 2), (44, 2), (4, 4), (16, 4)]
+    };
+    let phentsize = field(&header, phentsize)?.max(1);
+    let mut headers = vec![0; (phentsize * field(&header, phnum)?).min(1 << 16) as usize];
+    let len = event.read_at(&mut headers, field(&header, phoff)?).ok()?;
+    let interp = headers[..len]
+        .chunks_exact(phentsize as usize)
+        .find(|header| field(header, (0, 4)) == Some(PT_INTERP))?;
+    let mut path = vec![0; field(interp, filesz)?.min(libc::PATH_MAX as u64) as usize];
+    let len = event.read_at(&mut path, field(interp, offset)?).ok()?;
+    let path = std::str::from_utf8(&path[..len])
+        .ok()?
+        .trim_end_matches('\0');
+    let meta = metadata(format!("/proc/{}/root{}", event.pid, path)).ok()?;
+    Some((meta.dev(), meta.ino()))
+}
+
+pub(crate) fn dev_ino(fd: i32) -> Option<(u64, u64)> {
+    let stat = fstat(fd).ok()?;
+    Some((stat.st_dev, stat.st_ino))
+}
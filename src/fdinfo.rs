@@ -0,0 +1,310 @@
+use crate::high_level::FanotifyMode;
+use crate::low_level::{
+    FAN_CLASS_CONTENT, FAN_CLASS_PRE_CONTENT, FAN_EVENT_ON_CHILD, FAN_MARK_EVICTABLE,
+};
+use crate::registry::{Mark, MarkKind};
+use std::fs::{read_dir, read_link, read_to_string};
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+
+/// The state of a fanotify group as reported by the kernel in `/proc/<pid>/fdinfo/<fd>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupState {
+    /// Flags the group was initialized with, including the class.
+    pub flags: u32,
+    /// Flags of the file descriptors opened for events.
+    pub event_flags: u32,
+    pub marks: Vec<KernelMark>,
+}
+
+/// What a mark reported by the kernel is attached to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KernelMarkTarget {
+    Inode {
+        ino: u64,
+        /// Device of the filesystem, in the kernel's internal encoding. See `KernelMark::dev`.
+        sdev: u32,
+        /// The file handle of the inode, if the kernel could encode one.
+        handle: Option<KernelFileHandle>,
+    },
+    Mount {
+        /// Id of the mount, as in `/proc/self/mountinfo`.
+        mnt_id: u64,
+    },
+    Filesystem {
+        /// Device of the filesystem, in the kernel's internal encoding. See `KernelMark::dev`.
+        sdev: u32,
+    },
+    MountNamespace {
+        /// Inode number of the namespace, as in the `mnt:[<ns>]` link of `/proc/<pid>/ns/mnt`.
+        ns: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelFileHandle {
+    pub handle_type: i32,
+    pub bytes: Vec<u8>,
+}
+
+/// A mark of a group as reported by the kernel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelMark {
+    pub target: KernelMarkTarget,
+    /// The `FAN_MARK_*` flags stored with the mark, e.g. `FAN_MARK_IGNORED_SURV_MODIFY`.
+    pub mflags: u32,
+    pub mask: u64,
+    pub ignored_mask: u64,
+}
+
+impl KernelMark {
+    pub fn kind(&self) -> MarkKind {
+        match self.target {
+            KernelMarkTarget::Inode { .. } => MarkKind::Inode,
+            KernelMarkTarget::Mount { .. } => MarkKind::Mount,
+            KernelMarkTarget::Filesystem { .. } => MarkKind::Filesystem,
+            KernelMarkTarget::MountNamespace { .. } => MarkKind::MountNamespace,
+        }
+    }
+
+    /// The device of the marked filesystem, encoded like `st_dev`, or `None` for mount and
+    /// mount namespace marks.
+    pub fn dev(&self) -> Option<u64> {
+        match self.target {
+            KernelMarkTarget::Inode { sdev, .. } | KernelMarkTarget::Filesystem { sdev } => {
+                Some(libc::makedev(sdev >> 20, sdev & 0xf_ffff))
+            }
+            KernelMarkTarget::Mount { .. } | KernelMarkTarget::MountNamespace { .. } => None,
+        }
+    }
+
+    /// Whether this is the kernel side of `mark`.
+    pub fn matches(&self, mark: &Mark) -> bool {
+        match self.target {
+            KernelMarkTarget::Inode { ino, .. } => {
+                mark.kind == MarkKind::Inode
+                    && mark.target.ino == ino
+                    && Some(mark.target.dev) == self.dev()
+            }
+            KernelMarkTarget::Mount { mnt_id } => {
+                mark.kind == MarkKind::Mount && mark.target.mnt_id == mnt_id
+            }
+            KernelMarkTarget::Filesystem { .. } => {
+                mark.kind == MarkKind::Filesystem && Some(mark.target.dev) == self.dev()
+            }
+            KernelMarkTarget::MountNamespace { ns } => {
+                mark.kind == MarkKind::MountNamespace && mark.target.ino == ns
+            }
+        }
+    }
+}
+
+/// A difference between the marks recorded by a `Fanotify` group and the marks the kernel
+/// reports for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkDrift {
+    /// The mark was added, but the kernel no longer has it, e.g. because the mount went away or
+    /// the inode was evicted.
+    Missing(Mark),
+    /// The kernel has a mark that was not added through the group, or was added by a call the
+    /// registry doesn't track.
+    Unexpected(KernelMark),
+    /// Both sides have the mark, with different masks. The kernel clears ignore masks without
+    /// `FAN_MARK_IGNORED_SURV_MODIFY` when the object is modified.
+    Changed { mark: Mark, kernel: KernelMark },
+}
+
+impl GroupState {
+    /// Reads the state of the fanotify group `fd` of process `pid`.
+    pub fn read(pid: i32, fd: i32) -> Result<Self, Error> {
+        Self::read_fdinfo(&format!("/proc/{}/fdinfo/{}", pid, fd))
+    }
+
+    pub(crate) fn read_fdinfo(path: &str) -> Result<Self, Error> {
+        parse(&read_to_string(path)?).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{} is not a fanotify file descriptor", path),
+            )
+        })
+    }
+
+    pub fn class(&self) -> FanotifyMode {
+        match self.flags & (FAN_CLASS_CONTENT | FAN_CLASS_PRE_CONTENT) {
+            FAN_CLASS_PRE_CONTENT => FanotifyMode::PRECONTENT,
+            FAN_CLASS_CONTENT => FanotifyMode::CONTENT,
+            _ => FanotifyMode::NOTIF,
+        }
+    }
+
+    /// The kernel mark corresponding to `mark`, if any.
+    pub fn find(&self, mark: &Mark) -> Option<&KernelMark> {
+        self.marks.iter().find(|kernel| kernel.matches(mark))
+    }
+
+    /// Compares the marks recorded by a group with the ones the kernel reports.
+    pub fn drift(&self, marks: &[Mark]) -> Vec<MarkDrift> {
+        let mut drift = Vec::new();
+        for mark in marks {
+            match self.find(mark) {
+                None => drift.push(MarkDrift::Missing(mark.clone())),
+                // Groups reporting directory fids get FAN_EVENT_ON_CHILD added by the kernel
+                // to the marks of mounts, filesystems and non-directories.
+                Some(kernel)
+                    if kernel.mask & (mark.mask | !FAN_EVENT_ON_CHILD) != mark.mask
+                        || kernel.ignored_mask != mark.ignored_mask =>
+                {
+                    drift.push(MarkDrift::Changed {
+                        mark: mark.clone(),
+                        kernel: kernel.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        // Evictable marks (see `Fanotify::cache_clean`) aren't recorded by the registry.
+        for kernel in &self.marks {
+            if kernel.mflags & FAN_MARK_EVICTABLE == 0
+                && !marks.iter().any(|mark| kernel.matches(mark))
+            {
+                drift.push(MarkDrift::Unexpected(kernel.clone()));
+            }
+        }
+        drift
+    }
+}
+
+/// A fanotify group found in a process by `scan_groups`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupHolder {
+    pub pid: i32,
+    /// The executable of the process, if it could be read.
+    pub exe: Option<PathBuf>,
+    /// The file descriptor of the group in the process.
+    pub fd: i32,
+    pub state: GroupState,
+}
+
+/// Finds the fanotify groups held by every process on the system, by scanning
+/// `/proc/*/fd` for fanotify file descriptors and parsing their fdinfo.
+///
+/// Processes whose file descriptors can't be read (not enough privileges, or the process
+/// exited during the scan) are skipped. Reading other users' processes requires
+/// `CAP_SYS_PTRACE` or root.
+pub fn scan_groups() -> Result<Vec<GroupHolder>, Error> {
+    let mut groups = Vec::new();
+    for entry in read_dir("/proc")? {
+        let Some(pid) = entry?
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<i32>().ok())
+        else {
+            continue;
+        };
+        groups.extend(process_groups(pid).unwrap_or_default());
+    }
+    Ok(groups)
+}
+
+/// Finds the fanotify groups held by process `pid`.
+pub fn process_groups(pid: i32) -> Result<Vec<GroupHolder>, Error> {
+    let mut groups = Vec::new();
+    for entry in read_dir(format!("/proc/{}/fd", pid))? {
+        let entry = entry?;
+        let Some(fd) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<i32>().ok())
+        else {
+            continue;
+        };
+        if !read_link(entry.path())
+            .is_ok_and(|target| target.as_os_str() == "anon_inode:[fanotify]")
+        {
+            continue;
+        }
+        if let Ok(state) = GroupState::read(pid, fd) {
+            groups.push(GroupHolder {
+                pid,
+                exe: read_link(format!("/proc/{}/exe", pid)).ok(),
+                fd,
+                state,
+            });
+        }
+    }
+    Ok(groups)
+}
+
+/// Parses the contents of the fdinfo file of a fanotify group. Returns `None` if it doesn't
+/// describe a fanotify group.
+///
+/// ```
+/// let state = fanotify::fdinfo::parse(
+///     "pos:\t0\nflags:\t02\nfanotify flags:10 event-flags:0\n\
+///      fanotify mnt_id:1c mflags:0 mask:20 ignored_mask:0\n",
+/// )
+/// .unwrap();
+/// assert_eq!(state.flags, 0x10);
+/// assert_eq!(state.marks[0].mask, fanotify::low_level::FAN_OPEN);
+/// ```
+pub fn parse(contents: &str) -> Option<GroupState> {
+    let mut state: Option<GroupState> = None;
+    for line in contents.lines() {
+        let Some(fields) = line.strip_prefix("fanotify ") else {
+            continue;
+        };
+        let field = |name: &str| {
+            fields
+                .split_whitespace()
+                .find_map(|field| field.strip_prefix(name)?.strip_prefix(':'))
+        };
+        let hex = |name: &str| field(name).and_then(|value| u64::from_str_radix(value, 16).ok());
+        if let (Some(flags), Some(event_flags)) = (hex("flags"), hex("event-flags")) {
+            let group = state.get_or_insert_with(GroupState::default);
+            group.flags = flags as u32;
+            group.event_flags = event_flags as u32;
+            continue;
+        }
+        let target = if let Some(ns) = field("mnt_ns").and_then(|ns| ns.parse().ok()) {
+            KernelMarkTarget::MountNamespace { ns }
+        } else if let Some(mnt_id) = hex("mnt_id") {
+            KernelMarkTarget::Mount { mnt_id }
+        } else if let (Some(ino), Some(sdev)) = (hex("ino"), hex("sdev")) {
+            let handle =
+                field("fhandle-type")
+                    .zip(field("f_handle"))
+                    .and_then(|(handle_type, bytes)| {
+                        Some(KernelFileHandle {
+                            handle_type: i32::from_str_radix(handle_type, 16).ok()?,
+                            bytes: decode_hex(bytes)?,
+                        })
+                    });
+            KernelMarkTarget::Inode {
+                ino,
+                sdev: sdev as u32,
+                handle,
+            }
+        } else if let Some(sdev) = hex("sdev") {
+            KernelMarkTarget::Filesystem { sdev: sdev as u32 }
+        } else {
+            continue;
+        };
+        state
+            .get_or_insert_with(GroupState::default)
+            .marks
+            .push(KernelMark {
+                target,
+                mflags: hex("mflags").unwrap_or(0) as u32,
+                mask: hex("mask").unwrap_or(0),
+                ignored_mask: hex("ignored_mask").unwrap_or(0),
+            });
+    }
+    state
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -1,24 +1,44 @@
+use crate::container::ContainerId;
+use crate::error::{Call, FanotifyError};
+use crate::fdinfo::{GroupState, MarkDrift};
 use crate::low_level::{
-    close_fd, fanotify_init, fanotify_mark, fanotify_read, FanotifyEventMetadata, AT_FDCWD,
-    FAN_ALLOW, FAN_CLASS_CONTENT, FAN_CLASS_NOTIF, FAN_CLASS_PRE_CONTENT, FAN_CLOEXEC, FAN_DENY,
-    FAN_MARK_ADD, FAN_MARK_FLUSH, FAN_MARK_MOUNT, FAN_MARK_REMOVE, FAN_NONBLOCK, O_CLOEXEC,
-    O_RDONLY,
+    close_fd, fanotify_init, fanotify_mark, fanotify_mark_fd, fanotify_read_info,
+    FanotifyEventInfo, FanotifyEventMetadata, AT_FDCWD, FAN_ALLOW, FAN_CLASS_CONTENT,
+    FAN_CLASS_NOTIF, FAN_CLASS_PRE_CONTENT, FAN_CLOEXEC, FAN_DENY, FAN_ENABLE_AUDIT,
+    FAN_EVENT_INFO_TYPE_MNT, FAN_EVENT_INFO_TYPE_PIDFD, FAN_MARK_ADD, FAN_MARK_DONT_FOLLOW,
+    FAN_MARK_EVICTABLE, FAN_MARK_FILESYSTEM, FAN_MARK_FLUSH, FAN_MARK_IGNORE,
+    FAN_MARK_IGNORED_MASK, FAN_MARK_IGNORED_SURV_MODIFY, FAN_MARK_MNTNS, FAN_MARK_MOUNT,
+    FAN_MARK_ONLYDIR, FAN_MARK_REMOVE, FAN_NOFD, FAN_NONBLOCK, FAN_REPORT_DIR_FID, FAN_REPORT_FID,
+    FAN_REPORT_MNT, FAN_REPORT_NAME, FAN_REPORT_PIDFD, FAN_REPORT_TID, FAN_UNLIMITED_MARKS,
+    FAN_UNLIMITED_QUEUE, O_CLOEXEC, O_LARGEFILE, O_NOATIME, O_RDONLY, O_RDWR, O_WRONLY,
 };
+use crate::mountinfo::{path_in_process, ProcessPath};
+use crate::process::{Ancestor, ProcessCache, ProcessCacheConfig, ProcessInfo};
+use crate::registry::{Mark, MarkId, MarkRegistry, MarkSnapshot};
 use crate::FanotifyPath;
 use enum_iterator::{all, Sequence};
+use std::ffi::{CString, OsStr};
 use std::fs::read_link;
-use std::io::Error;
-use std::os::fd::{AsFd, BorrowedFd};
+use std::io::{Read, Seek, SeekFrom};
+use std::mem::MaybeUninit;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub use crate::low_level::{
     FAN_ACCESS, FAN_ACCESS_PERM, FAN_ATTRIB, FAN_CLOSE, FAN_CLOSE_NOWRITE, FAN_CLOSE_WRITE,
-    FAN_CREATE, FAN_DELETE, FAN_DELETE_SELF, FAN_EVENT_ON_CHILD, FAN_MODIFY, FAN_MOVE,
-    FAN_MOVED_FROM, FAN_MOVED_TO, FAN_MOVE_SELF, FAN_ONDIR, FAN_OPEN, FAN_OPEN_EXEC,
-    FAN_OPEN_EXEC_PERM, FAN_OPEN_PERM,
+    FAN_CREATE, FAN_DELETE, FAN_DELETE_SELF, FAN_EVENT_ON_CHILD, FAN_MNT_ATTACH, FAN_MNT_DETACH,
+    FAN_MODIFY, FAN_MOVE, FAN_MOVED_FROM, FAN_MOVED_TO, FAN_MOVE_SELF, FAN_ONDIR, FAN_OPEN,
+    FAN_OPEN_EXEC, FAN_OPEN_EXEC_PERM, FAN_OPEN_PERM,
 };
 
 pub struct Fanotify {
     fd: i32,
+    config: Option<FanotifyBuilder>,
+    marks: Arc<Mutex<MarkRegistry>>,
+    processes: Option<Arc<Mutex<ProcessCache>>>,
 }
 
 // SAFETY: the `fanotify_*` functions are thread safe, and file descriptors are safe for
@@ -37,11 +57,21 @@ where
     T: Into<i32>,
 {
     fn from(raw: T) -> Fanotify {
-        Fanotify { fd: raw.into() }
+        Fanotify {
+            fd: raw.into(),
+            config: None,
+            marks: Arc::default(),
+            processes: None,
+        }
     }
 }
 
 #[derive(Debug, Clone, Copy, Sequence, PartialEq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum FanEvent {
     Access = FAN_ACCESS as isize,
     AccessPerm = FAN_ACCESS_PERM as isize,
@@ -53,6 +83,8 @@ pub enum FanEvent {
     Delete = FAN_DELETE as isize,
     DeleteSelf = FAN_DELETE_SELF as isize,
     EventOnChild = FAN_EVENT_ON_CHILD as isize,
+    MntAttach = FAN_MNT_ATTACH as isize,
+    MntDetach = FAN_MNT_DETACH as isize,
     Modify = FAN_MODIFY as isize,
     Move = FAN_MOVE as isize,
     MovedFrom = FAN_MOVED_FROM as isize,
@@ -78,6 +110,8 @@ impl From<FanEvent> for u64 {
             FanEvent::Delete => FAN_DELETE,
             FanEvent::DeleteSelf => FAN_DELETE_SELF,
             FanEvent::EventOnChild => FAN_EVENT_ON_CHILD,
+            FanEvent::MntAttach => FAN_MNT_ATTACH,
+            FanEvent::MntDetach => FAN_MNT_DETACH,
             FanEvent::Modify => FAN_MODIFY,
             FanEvent::Move => FAN_MOVE,
             FanEvent::MovedFrom => FAN_MOVED_FROM,
@@ -119,23 +153,295 @@ pub struct Event {
     pub path: String,
     pub events: Vec<FanEvent>,
     pub pid: i32,
+    /// The information records that followed the event, see `EventInfo`.
+    pub info: Vec<EventInfo>,
+    /// The process that caused the event, filled in by `Fanotify::read_event` for groups
+    /// built with `FanotifyBuilder::process_info`. `None` otherwise, or if the process
+    /// couldn't be read (e.g. it already exited).
+    pub process: Option<ProcessInfo>,
+    /// The pidfd of the `EventInfo::Pidfd` record, see `Event::pidfd`.
+    pidfd: Option<OwnedFd>,
+    ancestors: Vec<Ancestor>,
+    stat: OnceLock<Option<FileStat>>,
+}
+
+/// The metadata of the file of an event, see `Event::stat`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FileStat {
+    pub dev: u64,
+    pub ino: u64,
+    /// File type and permission bits, as in `st_mode`.
+    pub mode: u32,
+    pub nlink: u64,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub mtime: SystemTime,
+}
+
+impl FileStat {
+    fn from_stat(stat: &libc::stat) -> Self {
+        // The nanoseconds count forward from the seconds, even before the epoch.
+        let secs = Duration::from_secs(stat.st_mtime.unsigned_abs());
+        let nanos = Duration::from_nanos(stat.st_mtime_nsec as u64);
+        Self {
+            dev: stat.st_dev,
+            ino: stat.st_ino,
+            mode: stat.st_mode,
+            nlink: stat.st_nlink,
+            uid: stat.st_uid,
+            gid: stat.st_gid,
+            size: stat.st_size as u64,
+            mtime: if stat.st_mtime >= 0 {
+                UNIX_EPOCH + secs + nanos
+            } else {
+                UNIX_EPOCH - secs + nanos
+            },
+        }
+    }
+
+    pub fn is_regular(&self) -> bool {
+        self.mode & libc::S_IFMT == libc::S_IFREG
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.mode & libc::S_IFMT == libc::S_IFDIR
+    }
+
+    /// Whether the file is a regular file with an execute bit set for anyone. This only looks
+    /// at the mode bits: it doesn't tell whether a given process may execute the file.
+    pub fn is_executable(&self) -> bool {
+        self.is_regular() && self.mode & 0o111 != 0
+    }
+}
+
+/// An information record of an event. Which records an event carries depends on the
+/// `FAN_REPORT_*` flags of the group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventInfo {
+    /// The mount a `FAN_MNT_ATTACH` or `FAN_MNT_DETACH` event is about (`FAN_REPORT_MNT`).
+    Mount {
+        /// The unique 64-bit id of the mount, as reported by `statx` with `STATX_MNT_ID_UNIQUE`
+        /// and by `statmount(2)`. It differs from the id listed in `/proc/self/mountinfo`.
+        mnt_id: u64,
+    },
+    /// A pidfd of the process that caused the event was reported (`FAN_REPORT_PIDFD`). The
+    /// descriptor is owned by the `Event`, see `Event::pidfd`; converting a record on its own
+    /// doesn't take it over.
+    Pidfd,
+    /// A record this crate doesn't parse, without its header.
+    Other { info_type: u8, data: Vec<u8> },
+}
+
+impl From<FanotifyEventInfo> for EventInfo {
+    fn from(info: FanotifyEventInfo) -> Self {
+        match info.info_type {
+            // The id is 8-byte aligned in the record, after 4 bytes of padding.
+            FAN_EVENT_INFO_TYPE_MNT if info.data.len() >= 12 => EventInfo::Mount {
+                mnt_id: u64::from_ne_bytes(info.data[4..12].try_into().unwrap()),
+            },
+            FAN_EVENT_INFO_TYPE_PIDFD if info.data.len() >= 4 => EventInfo::Pidfd,
+            _ => EventInfo::Other {
+                info_type: info.info_type,
+                data: info.data,
+            },
+        }
+    }
+}
+
+/// A mount attached to or detached from a mount namespace marked with
+/// `Fanotify::add_mount_namespace`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MountEvent {
+    Attach { mnt_id: u64 },
+    Detach { mnt_id: u64 },
+}
+
+impl MountEvent {
+    /// The unique 64-bit id of the mount, see `EventInfo::Mount`.
+    pub fn mnt_id(&self) -> u64 {
+        match self {
+            MountEvent::Attach { mnt_id } | MountEvent::Detach { mnt_id } => *mnt_id,
+        }
+    }
 }
 
 impl Event {
     /// Tries to duplicate the event, including duplicating the file descriptor
     /// so new instances can keep it open after the original calls `Drop::drop`.
     pub fn try_clone(&self) -> Result<Self, std::io::Error> {
-        let new_fd = unsafe { libc::dup(self.fd) };
-        if new_fd < 0 {
-            return Err(std::io::Error::last_os_error());
-        }
-        Ok(Self {
-            fd: new_fd,
+        let mut event = Self {
+            fd: FAN_NOFD,
             path: self.path.clone(),
             events: self.events.clone(),
             pid: self.pid,
+            info: self.info.clone(),
+            process: self.process.clone(),
+            pidfd: self.pidfd.as_ref().map(OwnedFd::try_clone).transpose()?,
+            ancestors: self.ancestors.clone(),
+            stat: self.stat.clone(),
+        };
+        // Set once duplicated, so that `event` closes the pidfd on error.
+        if self.fd >= 0 {
+            event.fd = dup(self.fd)?;
+        }
+        Ok(event)
+    }
+
+    /// A pidfd of the process that caused the event, when the group was built with
+    /// `FanotifyBuilder::report_pidfd` (since Linux 5.15).
+    ///
+    /// Unlike `pid`, the pidfd refers to the very process that caused the event even if it
+    /// exited and its pid was reused since: signals sent with `pidfd_send_signal(2)` can't reach
+    /// another process, and `/proc/<pid>` can be checked to still belong to it with
+    /// `pidfd_send_signal(pidfd, 0, ...)` after reading it.
+    ///
+    /// Each call returns a new descriptor. Returns `None` if the group doesn't report pidfds,
+    /// the process exited before the event was read (`FAN_NOPIDFD`), or the kernel couldn't
+    /// open a pidfd (`FAN_EPIDFD`).
+    pub fn pidfd(&self) -> Option<OwnedFd> {
+        self.borrow_pidfd()?.try_clone_to_owned().ok()
+    }
+
+    /// Borrows the file descriptor of the event, e.g. to pass it to `Fanotify::cache_clean`.
+    /// Returns `None` for events without one (`FAN_NOFD`): events of groups reporting file
+    /// ids, and queue overflows.
+    pub fn try_as_fd(&self) -> Option<BorrowedFd<'_>> {
+        (self.fd >= 0).then(|| unsafe { BorrowedFd::borrow_raw(self.fd) })
+    }
+
+    /// The path of the file of the event as the process that caused it sees it, which differs
+    /// from `path` when the process runs in another mount namespace or root directory (e.g. in
+    /// a container). Flags files that were deleted or are on detached mounts.
+    /// See `mountinfo::path_in_process`.
+    ///
+    /// Returns `ProcessPath::Unresolved` for events without a file descriptor.
+    pub fn path_in_process_ns(&self) -> ProcessPath {
+        match self.try_as_fd() {
+            Some(fd) => path_in_process(&fd, self.pid),
+            None => ProcessPath::Unresolved,
+        }
+    }
+
+    /// The metadata of the file of the event, from `fstat` on its file descriptor. It is read
+    /// on the first call and kept for the next ones, or when the event is read for groups
+    /// built with `FanotifyBuilder::stat_on_read`, so that it reflects the file at the time
+    /// of the event rather than when it is handled.
+    ///
+    /// Returns `None` for events without a file descriptor.
+    pub fn stat(&self) -> Option<&FileStat> {
+        self.stat
+            .get_or_init(|| {
+                if self.fd < 0 {
+                    return None;
+                }
+                fstat(self.fd).ok().map(|stat| FileStat::from_stat(&stat))
+            })
+            .as_ref()
+    }
+
+    /// Whether the file of the event is a regular file, see `stat`.
+    pub fn is_regular(&self) -> bool {
+        self.stat().is_some_and(FileStat::is_regular)
+    }
+
+    /// Whether the file of the event is a directory, see `stat`.
+    pub fn is_dir(&self) -> bool {
+        self.stat().is_some_and(FileStat::is_dir)
+    }
+
+    /// Whether the file of the event is an executable regular file, see `FileStat::is_executable`.
+    pub fn is_executable(&self) -> bool {
+        self.stat().is_some_and(FileStat::is_executable)
+    }
+
+    /// Reads from the file of the event at `offset`, with `pread`, so that the offset of the
+    /// file descriptor, which is shared with the other holders of the event, is left alone.
+    ///
+    /// The file descriptors of events are opened with `FMODE_NONOTIFY`, so reading them
+    /// doesn't generate events for this group or any other. This holds for `reader` and
+    /// `read_prefix` too, which read through the same open file.
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize, std::io::Error> {
+        if self.fd < 0 {
+            return Err(std::io::Error::from_raw_os_error(libc::EBADF));
+        }
+        pread(self.fd, buf, offset)
+    }
+
+    /// Reads up to `n` bytes from the start of the file of the event, e.g. to look at the
+    /// magic bytes of its format. Fewer bytes are returned if the file is shorter.
+    pub fn read_prefix(&self, n: usize) -> Result<Vec<u8>, std::io::Error> {
+        // The buffer grows as bytes are read, so a large `n` costs nothing on a small file.
+        let mut prefix = Vec::new();
+        self.reader()?.take(n as u64).read_to_end(&mut prefix)?;
+        Ok(prefix)
+    }
+
+    /// A reader over the file of the event, starting at offset 0. It owns a duplicate of the
+    /// file descriptor, so it can outlive the event, and keeps its own position.
+    pub fn reader(&self) -> Result<EventReader, std::io::Error> {
+        if self.fd < 0 {
+            return Err(std::io::Error::from_raw_os_error(libc::EBADF));
+        }
+        Ok(EventReader {
+            fd: unsafe { OwnedFd::from_raw_fd(dup(self.fd)?) },
+            position: 0,
         })
     }
+
+    /// Whether the file of the event was deleted, i.e. it has no links left (`st_nlink` is 0).
+    /// The ` (deleted)` suffix the kernel appends to the path of such files is stripped from
+    /// `path`, as long as the file was already deleted when the event was read.
+    ///
+    /// Returns `false` for events without a file descriptor.
+    pub fn is_deleted(&self) -> bool {
+        self.fd >= 0 && is_unlinked(self.fd)
+    }
+
+    /// The container of the process that caused the event, if its process was looked up
+    /// (see `Event::process`) and runs in a container.
+    pub fn container(&self) -> Option<&ContainerId> {
+        self.process.as_ref()?.container.as_ref()
+    }
+
+    /// The ancestors of the process that caused the event, from its parent up, filled in by
+    /// `Fanotify::read_event` for groups built with `FanotifyBuilder::process_ancestry`.
+    /// See `ProcessCache::ancestors`.
+    pub fn ancestors(&self) -> &[Ancestor] {
+        &self.ancestors
+    }
+
+    fn borrow_pidfd(&self) -> Option<BorrowedFd<'_>> {
+        self.pidfd.as_ref().map(OwnedFd::as_fd)
+    }
+
+    /// Reads the information of the process that caused the event from `/proc`. The pidfd of
+    /// the event is used when the group reports one, see `ProcessInfo::from_pidfd`.
+    ///
+    /// For permission events the process is blocked until the response is sent, so it can be
+    /// read reliably. For other events it may have exited, or even been replaced by another
+    /// process with the same pid when there is no pidfd to tell them apart.
+    pub fn process_info(&self) -> Result<ProcessInfo, std::io::Error> {
+        match self.borrow_pidfd() {
+            Some(pidfd) => ProcessInfo::from_pidfd(&pidfd),
+            None => ProcessInfo::read(self.pid),
+        }
+    }
+
+    /// The mount event this event describes, if it is one.
+    pub fn mount_event(&self) -> Option<MountEvent> {
+        let mnt_id = self.info.iter().find_map(|info| match info {
+            EventInfo::Mount { mnt_id } => Some(*mnt_id),
+            _ => None,
+        })?;
+        if self.events.contains(&FanEvent::MntAttach) {
+            Some(MountEvent::Attach { mnt_id })
+        } else if self.events.contains(&FanEvent::MntDetach) {
+            Some(MountEvent::Detach { mnt_id })
+        } else {
+            None
+        }
+    }
 }
 
 impl Drop for Event {
@@ -144,19 +450,146 @@ impl Drop for Event {
     }
 }
 
+/// A `Read + Seek` adapter over the file of an event, see `Event::reader`.
+///
+/// A duplicated file descriptor shares its offset with the original, so the reader tracks
+/// its own position and reads with `pread`: the offset seen by the other holders of the
+/// event never moves.
+#[derive(Debug)]
+pub struct EventReader {
+    fd: OwnedFd,
+    position: u64,
+}
+
+impl EventReader {
+    /// Reads at `offset` without moving the position of the reader.
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize, std::io::Error> {
+        pread(self.fd.as_raw_fd(), buf, offset)
+    }
+}
+
+impl Read for EventReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        let read = self.read_at(buf, self.position)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for EventReader {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, std::io::Error> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => {
+                self.position = offset;
+                return Ok(offset);
+            }
+            SeekFrom::Current(offset) => (self.position, offset),
+            SeekFrom::End(offset) => (fstat(self.fd.as_raw_fd())?.st_size as u64, offset),
+        };
+        self.position = base.checked_add_signed(offset).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+impl AsFd for EventReader {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+fn pread(fd: i32, buf: &mut [u8], offset: u64) -> Result<usize, std::io::Error> {
+    let read = unsafe {
+        libc::pread(
+            fd,
+            buf.as_mut_ptr() as *mut libc::c_void,
+            buf.len(),
+            offset as libc::off_t,
+        )
+    };
+    if read < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(read as usize)
+}
+
+fn dup(fd: i32) -> Result<i32, std::io::Error> {
+    match unsafe { libc::dup(fd) } {
+        -1 => Err(std::io::Error::last_os_error()),
+        fd => Ok(fd),
+    }
+}
+
 impl From<FanotifyEventMetadata> for Event {
     fn from(metadata: FanotifyEventMetadata) -> Self {
-        let path = read_link(format!("/proc/self/fd/{}", metadata.fd)).unwrap_or_default();
+        Event::from((metadata, Vec::new()))
+    }
+}
+
+impl From<(FanotifyEventMetadata, Vec<FanotifyEventInfo>)> for Event {
+    fn from((metadata, info): (FanotifyEventMetadata, Vec<FanotifyEventInfo>)) -> Self {
         Event {
             fd: metadata.fd,
-            path: path.to_str().unwrap().to_string(),
+            path: fd_path(metadata.fd),
             events: events_from_mask(metadata.mask),
             pid: metadata.pid,
+            pidfd: info
+                .iter()
+                .find_map(FanotifyEventInfo::pidfd)
+                .map(|pidfd| unsafe { OwnedFd::from_raw_fd(pidfd) }),
+            info: info.into_iter().map(EventInfo::from).collect(),
+            process: None,
+            ancestors: Vec::new(),
+            stat: OnceLock::new(),
         }
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+/// Resolves the path of an event's file descriptor. Groups reporting file ids receive
+/// `FAN_NOFD` instead of a descriptor, in which case the path is empty.
+fn fd_path(fd: i32) -> String {
+    if fd == FAN_NOFD {
+        return String::new();
+    }
+    let path = read_link(format!("/proc/self/fd/{}", fd)).unwrap_or_default();
+    strip_deleted(fd, path).to_str().unwrap().to_string()
+}
+
+/// What the kernel appends to the link of a deleted file in `/proc/<pid>/fd`.
+const DELETED_SUFFIX: &[u8] = b" (deleted)";
+
+/// Strips the ` (deleted)` suffix the kernel appends to `link`, the path of `fd`, if the file
+/// was deleted. Files whose name merely ends with it are left alone, as they still have links.
+pub(crate) fn strip_deleted(fd: i32, link: PathBuf) -> PathBuf {
+    match link.as_os_str().as_bytes().strip_suffix(DELETED_SUFFIX) {
+        Some(path) if is_unlinked(fd) => PathBuf::from(OsStr::from_bytes(path)),
+        _ => link,
+    }
+}
+
+/// Whether the file of `fd` has no links left, i.e. it was deleted while open.
+pub(crate) fn is_unlinked(fd: i32) -> bool {
+    fstat(fd).is_ok_and(|stat| stat.st_nlink == 0)
+}
+
+pub(crate) fn fstat(fd: i32) -> Result<libc::stat, std::io::Error> {
+    let mut stat = MaybeUninit::<libc::stat>::zeroed();
+    if unsafe { libc::fstat(fd, stat.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { stat.assume_init() })
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 #[repr(C)]
 pub enum FanotifyMode {
     PRECONTENT,
@@ -174,63 +607,525 @@ impl FanotifyMode {
     }
 }
 
+fn init(flags: u32, event_f_flags: u32) -> Result<i32, FanotifyError> {
+    fanotify_init(flags, event_f_flags).map_err(|e| {
+        FanotifyError::from_os(
+            Call::Init {
+                flags,
+                event_f_flags,
+            },
+            e,
+        )
+    })
+}
+
+/// Access mode of the file descriptors opened for events.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum EventAccess {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite,
+}
+
+impl EventAccess {
+    fn to_open_flags(self) -> u32 {
+        (match self {
+            EventAccess::ReadOnly => O_RDONLY,
+            EventAccess::WriteOnly => O_WRONLY,
+            EventAccess::ReadWrite => O_RDWR,
+        }) as u32
+    }
+}
+
+/// How an ignore mask added by `Fanotify::ignore_path` and friends behaves.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct IgnoreOptions {
+    /// Keep ignoring the events after the object is modified (`FAN_MARK_IGNORED_SURV_MODIFY`).
+    /// Otherwise the kernel clears the ignore mask on the first modification.
+    pub survive_modify: bool,
+    /// Also ignore the events on directories. This uses `FAN_MARK_IGNORE` (since Linux 6.0)
+    /// with `FAN_ONDIR` instead of the legacy `FAN_MARK_IGNORED_MASK`.
+    ///
+    /// The kernel only accepts `FAN_MARK_IGNORE` on directories, mounts and filesystems along
+    /// with `FAN_MARK_IGNORED_SURV_MODIFY`, so the ignore masks of these marks always survive
+    /// modifications, whatever `survive_modify` says.
+    pub ondir: bool,
+    /// Let the kernel drop the mark when the inode is evicted from memory (`FAN_MARK_EVICTABLE`,
+    /// since Linux 5.19). Only valid for inode marks.
+    pub evictable: bool,
+}
+
+impl IgnoreOptions {
+    pub(crate) fn to_mark_flags(self) -> u32 {
+        let mut flags = if self.ondir {
+            FAN_MARK_IGNORE
+        } else {
+            FAN_MARK_IGNORED_MASK
+        };
+        if self.survive_modify {
+            flags |= FAN_MARK_IGNORED_SURV_MODIFY;
+        }
+        if self.evictable {
+            flags |= FAN_MARK_EVICTABLE;
+        }
+        flags
+    }
+
+    pub(crate) fn to_mask(self, mode: u64) -> u64 {
+        if self.ondir {
+            mode | FAN_ONDIR
+        } else {
+            mode
+        }
+    }
+}
+
+/// How `Fanotify::add_at` and `Fanotify::remove_at` resolve their path.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct LookupOptions {
+    /// If the path is a symbolic link, mark the link itself (`FAN_MARK_DONT_FOLLOW`).
+    pub dont_follow: bool,
+    /// Fail with `ENOTDIR` if the path is not a directory (`FAN_MARK_ONLYDIR`).
+    pub only_dir: bool,
+}
+
+impl LookupOptions {
+    fn to_mark_flags(self) -> u32 {
+        let mut flags = 0;
+        if self.dont_follow {
+            flags |= FAN_MARK_DONT_FOLLOW;
+        }
+        if self.only_dir {
+            flags |= FAN_MARK_ONLYDIR;
+        }
+        flags
+    }
+}
+
+pub(crate) fn unignore_flags(mode: u64) -> u32 {
+    if mode & FAN_ONDIR != 0 {
+        FAN_MARK_IGNORE
+    } else {
+        FAN_MARK_IGNORED_MASK
+    }
+}
+
 impl Fanotify {
-    pub fn new_blocking(mode: FanotifyMode) -> Result<Self, Error> {
-        Ok(Fanotify {
-            fd: fanotify_init(
-                FAN_CLOEXEC | mode.to_fan_class(),
-                (O_CLOEXEC | O_RDONLY) as u32,
-            )?,
-        })
+    pub fn new_blocking(mode: FanotifyMode) -> Result<Self, FanotifyError> {
+        FanotifyBuilder::new()
+            .with_class(mode)
+            .with_event_flags((O_CLOEXEC | O_RDONLY) as u32)
+            .register()
     }
 
-    pub fn new_nonblocking(mode: FanotifyMode) -> Result<Self, Error> {
-        Ok(Fanotify {
-            fd: fanotify_init(
-                FAN_CLOEXEC | FAN_NONBLOCK | mode.to_fan_class(),
-                (O_CLOEXEC | O_RDONLY) as u32,
-            )?,
-        })
+    pub fn new_nonblocking(mode: FanotifyMode) -> Result<Self, FanotifyError> {
+        FanotifyBuilder::new()
+            .with_class(mode)
+            .with_flags(FAN_NONBLOCK)
+            .with_event_flags((O_CLOEXEC | O_RDONLY) as u32)
+            .register()
     }
 
-    pub fn add_path<P: ?Sized + FanotifyPath>(&self, mode: u64, path: &P) -> Result<(), Error> {
-        fanotify_mark(self.fd, FAN_MARK_ADD, mode, AT_FDCWD, path)?;
+    fn mark<P: ?Sized + FanotifyPath>(
+        &self,
+        flags: u32,
+        mask: u64,
+        path: &P,
+    ) -> Result<(), FanotifyError> {
+        self.mark_at(flags, mask, AT_FDCWD, Some(path.as_os_str()))
+    }
+
+    /// Marks `path` relative to `dirfd`, or `dirfd` itself if `path` is `None`.
+    pub(crate) fn mark_at(
+        &self,
+        flags: u32,
+        mask: u64,
+        dirfd: i32,
+        path: Option<&OsStr>,
+    ) -> Result<(), FanotifyError> {
+        let call = || Call::Mark {
+            flags,
+            mask,
+            path: path.map(PathBuf::from),
+        };
+        if let Err(reason) = validate_mark(self.config.as_ref(), flags, mask) {
+            return Err(FanotifyError::InvalidCombination {
+                call: call(),
+                reason,
+            });
+        }
+        // The registry keeps the flags asked for, so that the mark is replayed the same way.
+        let kernel_flags = implied_ignore_flags(flags, dirfd, path);
+        match path {
+            Some(path) => fanotify_mark(self.fd, kernel_flags, mask, dirfd, &path),
+            None => fanotify_mark_fd(self.fd, kernel_flags, mask, dirfd),
+        }
+        .map_err(|e| FanotifyError::from_os(call(), e))?;
+        self.registry().record(flags, mask, dirfd, path);
+        Ok(())
+    }
+
+    pub(crate) fn registry(&self) -> MutexGuard<'_, MarkRegistry> {
+        self.marks.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The marks added through this group (or its clones) that are still in place.
+    ///
+    /// Marks are tracked from the calls made through `Fanotify`: marks added with
+    /// `low_level::fanotify_mark`, evictable marks and marks the kernel drops by itself
+    /// (e.g. on unmount) are not reflected.
+    pub fn marks(&self) -> Vec<Mark> {
+        self.registry().marks()
+    }
+
+    /// Removes the mark `id` from the group, including its ignore mask. Returns the removed
+    /// mark, or `None` if no mark has this id.
+    pub fn remove_mark(&self, id: MarkId) -> Result<Option<Mark>, FanotifyError> {
+        let Some(mark) = self.registry().get(id).cloned() else {
+            return Ok(None);
+        };
+        let flags = FAN_MARK_REMOVE | mark.kind.to_mark_flags();
+        let (dirfd, path) = mark_location(&mark, flags)?;
+        if mark.mask != 0 {
+            self.mark_at(flags, mark.mask, dirfd, path)?;
+        }
+        if mark.ignored_mask != 0 {
+            self.mark_at(
+                flags | unignore_flags(mark.ignored_mask),
+                mark.ignored_mask,
+                dirfd,
+                path,
+            )?;
+        }
+        Ok(Some(mark))
+    }
+
+    /// Reads the flags and marks of the group as reported by the kernel.
+    pub fn kernel_state(&self) -> Result<GroupState, std::io::Error> {
+        GroupState::read_fdinfo(&format!("/proc/self/fdinfo/{}", self.fd))
+    }
+
+    /// Compares the marks recorded by the group (see `marks`) with the marks the kernel has.
+    pub fn drift(&self) -> Result<Vec<MarkDrift>, std::io::Error> {
+        Ok(self.kernel_state()?.drift(&self.marks()))
+    }
+
+    /// Takes a copy of the marks of the group, see `marks`.
+    pub fn snapshot(&self) -> MarkSnapshot {
+        self.registry().snapshot()
+    }
+
+    /// Adds the marks of `snapshot` to this group, e.g. to rebuild a group after a restart.
+    /// Marks are added again by path, so they apply to whatever the paths now refer to.
+    pub fn restore(&self, snapshot: &MarkSnapshot) -> Result<(), FanotifyError> {
+        for mark in snapshot.marks() {
+            let flags = FAN_MARK_ADD | mark.kind.to_mark_flags();
+            let (dirfd, path) = mark_location(mark, flags)?;
+            if mark.mask != 0 {
+                self.mark_at(flags, mark.mask, dirfd, path)?;
+            }
+            if mark.ignored_mask != 0 {
+                self.mark_at(
+                    flags | mark.ignore_options.to_mark_flags(),
+                    mark.ignored_mask,
+                    dirfd,
+                    path,
+                )?;
+            }
+        }
         Ok(())
     }
 
+    pub fn add_path<P: ?Sized + FanotifyPath>(
+        &self,
+        mode: u64,
+        path: &P,
+    ) -> Result<(), FanotifyError> {
+        self.mark(FAN_MARK_ADD, mode, path)
+    }
+
     pub fn add_mountpoint<P: ?Sized + FanotifyPath>(
         &self,
         mode: u64,
         path: &P,
-    ) -> Result<(), Error> {
-        fanotify_mark(self.fd, FAN_MARK_ADD | FAN_MARK_MOUNT, mode, AT_FDCWD, path)?;
-        Ok(())
+    ) -> Result<(), FanotifyError> {
+        self.mark(FAN_MARK_ADD | FAN_MARK_MOUNT, mode, path)
     }
 
-    pub fn remove_path<P: ?Sized + FanotifyPath>(&self, mode: u64, path: &P) -> Result<(), Error> {
-        fanotify_mark(self.fd, FAN_MARK_REMOVE, mode, AT_FDCWD, path)?;
-        Ok(())
+    pub fn remove_mountpoint<P: ?Sized + FanotifyPath>(
+        &self,
+        mode: u64,
+        path: &P,
+    ) -> Result<(), FanotifyError> {
+        self.mark(FAN_MARK_REMOVE | FAN_MARK_MOUNT, mode, path)
     }
 
-    pub fn flush_path<P: ?Sized + FanotifyPath>(&self, mode: u64, path: &P) -> Result<(), Error> {
-        fanotify_mark(self.fd, FAN_MARK_FLUSH, mode, AT_FDCWD, path)?;
-        Ok(())
+    /// Marks the whole filesystem containing `path`, as seen through any of its mounts
+    /// (bind mounts, overlays, other mount namespaces).
+    pub fn add_filesystem<P: ?Sized + FanotifyPath>(
+        &self,
+        mode: u64,
+        path: &P,
+    ) -> Result<(), FanotifyError> {
+        self.mark(FAN_MARK_ADD | FAN_MARK_FILESYSTEM, mode, path)
     }
 
+    pub fn remove_filesystem<P: ?Sized + FanotifyPath>(
+        &self,
+        mode: u64,
+        path: &P,
+    ) -> Result<(), FanotifyError> {
+        self.mark(FAN_MARK_REMOVE | FAN_MARK_FILESYSTEM, mode, path)
+    }
+
+    pub fn remove_path<P: ?Sized + FanotifyPath>(
+        &self,
+        mode: u64,
+        path: &P,
+    ) -> Result<(), FanotifyError> {
+        self.mark(FAN_MARK_REMOVE, mode, path)
+    }
+
+    pub fn flush_path<P: ?Sized + FanotifyPath>(
+        &self,
+        mode: u64,
+        path: &P,
+    ) -> Result<(), FanotifyError> {
+        self.mark(FAN_MARK_FLUSH, mode, path)
+    }
+
+    /// Adds `mode` to the ignore mask of the mark on `path`, so these events are dropped by the
+    /// kernel even if another mark of the group (e.g. on the mount) asks for them.
+    pub fn ignore_path<P: ?Sized + FanotifyPath>(
+        &self,
+        mode: u64,
+        path: &P,
+        options: IgnoreOptions,
+    ) -> Result<(), FanotifyError> {
+        self.mark(
+            FAN_MARK_ADD | options.to_mark_flags(),
+            options.to_mask(mode),
+            path,
+        )
+    }
+
+    /// Removes `mode` from the ignore mask of the mark on `path`. Directory events ignored with
+    /// `IgnoreOptions::ondir` are removed by including `FAN_ONDIR` in `mode`.
+    pub fn unignore_path<P: ?Sized + FanotifyPath>(
+        &self,
+        mode: u64,
+        path: &P,
+    ) -> Result<(), FanotifyError> {
+        self.mark(FAN_MARK_REMOVE | unignore_flags(mode), mode, path)
+    }
+
+    pub fn ignore_mountpoint<P: ?Sized + FanotifyPath>(
+        &self,
+        mode: u64,
+        path: &P,
+        options: IgnoreOptions,
+    ) -> Result<(), FanotifyError> {
+        self.mark(
+            FAN_MARK_ADD | FAN_MARK_MOUNT | options.to_mark_flags(),
+            options.to_mask(mode),
+            path,
+        )
+    }
+
+    pub fn unignore_mountpoint<P: ?Sized + FanotifyPath>(
+        &self,
+        mode: u64,
+        path: &P,
+    ) -> Result<(), FanotifyError> {
+        self.mark(
+            FAN_MARK_REMOVE | FAN_MARK_MOUNT | unignore_flags(mode),
+            mode,
+            path,
+        )
+    }
+
+    pub fn ignore_filesystem<P: ?Sized + FanotifyPath>(
+        &self,
+        mode: u64,
+        path: &P,
+        options: IgnoreOptions,
+    ) -> Result<(), FanotifyError> {
+        self.mark(
+            FAN_MARK_ADD | FAN_MARK_FILESYSTEM | options.to_mark_flags(),
+            options.to_mask(mode),
+            path,
+        )
+    }
+
+    pub fn unignore_filesystem<P: ?Sized + FanotifyPath>(
+        &self,
+        mode: u64,
+        path: &P,
+    ) -> Result<(), FanotifyError> {
+        self.mark(
+            FAN_MARK_REMOVE | FAN_MARK_FILESYSTEM | unignore_flags(mode),
+            mode,
+            path,
+        )
+    }
+
+    /// Marks the mount namespace `ns`, an opened `/proc/<pid>/ns/mnt`, to receive `FAN_MNT_ATTACH`
+    /// and `FAN_MNT_DETACH` events when mounts are added to or removed from it (since Linux 6.14).
+    /// The group must be built with `FanotifyBuilder::report_mnt`. See `Event::mount_event`.
+    pub fn add_mount_namespace<F: AsFd>(&self, mode: u64, ns: &F) -> Result<(), FanotifyError> {
+        self.mark_at(
+            FAN_MARK_ADD | FAN_MARK_MNTNS,
+            mode,
+            ns.as_fd().as_raw_fd(),
+            None,
+        )
+    }
+
+    pub fn remove_mount_namespace<F: AsFd>(&self, mode: u64, ns: &F) -> Result<(), FanotifyError> {
+        self.mark_at(
+            FAN_MARK_REMOVE | FAN_MARK_MNTNS,
+            mode,
+            ns.as_fd().as_raw_fd(),
+            None,
+        )
+    }
+
+    /// Marks the file or directory `fd` refers to. Unlike `add_path`, the object can't be
+    /// swapped (e.g. through a symlink) between the time it was opened and the time it is marked.
+    pub fn add_fd<F: AsFd>(&self, mode: u64, fd: &F) -> Result<(), FanotifyError> {
+        self.mark_at(FAN_MARK_ADD, mode, fd.as_fd().as_raw_fd(), None)
+    }
+
+    pub fn remove_fd<F: AsFd>(&self, mode: u64, fd: &F) -> Result<(), FanotifyError> {
+        self.mark_at(FAN_MARK_REMOVE, mode, fd.as_fd().as_raw_fd(), None)
+    }
+
+    pub fn ignore_fd<F: AsFd>(
+        &self,
+        mode: u64,
+        fd: &F,
+        options: IgnoreOptions,
+    ) -> Result<(), FanotifyError> {
+        self.mark_at(
+            FAN_MARK_ADD | options.to_mark_flags(),
+            options.to_mask(mode),
+            fd.as_fd().as_raw_fd(),
+            None,
+        )
+    }
+
+    pub fn unignore_fd<F: AsFd>(&self, mode: u64, fd: &F) -> Result<(), FanotifyError> {
+        self.mark_at(
+            FAN_MARK_REMOVE | unignore_flags(mode),
+            mode,
+            fd.as_fd().as_raw_fd(),
+            None,
+        )
+    }
+
+    /// Records that the file `fd` refers to (typically `Event::try_as_fd`) was scanned and found clean,
+    /// so further `FAN_OPEN_PERM` and `FAN_ACCESS_PERM` events for it are answered by the kernel
+    /// without reaching the group.
+    ///
+    /// The decision is kept as an evictable ignore mask: it goes away when the file is modified,
+    /// or when the kernel evicts the inode under memory pressure. It fails with `EEXIST` if the
+    /// inode already has a non-evictable mark in this group.
+    pub fn cache_clean<F: AsFd>(&self, fd: &F) -> Result<(), FanotifyError> {
+        self.ignore_fd(
+            FAN_OPEN_PERM | FAN_ACCESS_PERM,
+            fd,
+            IgnoreOptions {
+                evictable: true,
+                ..IgnoreOptions::default()
+            },
+        )
+    }
+
+    /// Marks `path` resolved relative to the directory `dirfd`.
+    pub fn add_at<F: AsFd, P: ?Sized + FanotifyPath>(
+        &self,
+        mode: u64,
+        dirfd: &F,
+        path: &P,
+        options: LookupOptions,
+    ) -> Result<(), FanotifyError> {
+        self.mark_at(
+            FAN_MARK_ADD | options.to_mark_flags(),
+            mode,
+            dirfd.as_fd().as_raw_fd(),
+            Some(path.as_os_str()),
+        )
+    }
+
+    pub fn remove_at<F: AsFd, P: ?Sized + FanotifyPath>(
+        &self,
+        mode: u64,
+        dirfd: &F,
+        path: &P,
+        options: LookupOptions,
+    ) -> Result<(), FanotifyError> {
+        self.mark_at(
+            FAN_MARK_REMOVE | options.to_mark_flags(),
+            mode,
+            dirfd.as_fd().as_raw_fd(),
+            Some(path.as_os_str()),
+        )
+    }
+
+    /// Removes all mount marks of the group.
+    pub fn flush_mounts(&self) -> Result<(), FanotifyError> {
+        self.mark(FAN_MARK_FLUSH | FAN_MARK_MOUNT, 0, "/")
+    }
+
+    /// Removes all filesystem marks of the group.
+    pub fn flush_filesystems(&self) -> Result<(), FanotifyError> {
+        self.mark(FAN_MARK_FLUSH | FAN_MARK_FILESYSTEM, 0, "/")
+    }
+
+    /// Reads the pending events. With `FanotifyBuilder::process_info`, the process of each
+    /// event is looked up as well, through the process cache of the group (see `Event::process`).
+    /// Exec events are recorded in the process tree of the cache, see `ProcessCache::record_exec`.
     pub fn read_event(&self) -> Vec<Event> {
-        let mut result = Vec::new();
-        let events = fanotify_read(self.fd);
-        for metadata in events {
-            let path = read_link(format!("/proc/self/fd/{}", metadata.fd)).unwrap_or_default();
-            let path = path.to_str().unwrap();
-            result.push(Event {
-                fd: metadata.fd,
-                path: String::from(path),
-                events: events_from_mask(metadata.mask),
-                pid: metadata.pid,
-            });
+        let mut events: Vec<Event> = fanotify_read_info(self.fd)
+            .into_iter()
+            .map(Event::from)
+            .collect();
+        if self.config.is_some_and(|config| config.has_stat_on_read()) {
+            for event in &events {
+                event.stat();
+            }
+        }
+        let ancestry = self
+            .config
+            .is_some_and(|config| config.has_process_ancestry());
+        if let Some(mut cache) = self.process_cache() {
+            cache.prune_if_due();
+            for event in &mut events {
+                event.process = cache.get(event.pid, event.borrow_pidfd()).ok();
+                cache.record_exec(event);
+                if ancestry {
+                    event.ancestors = cache.ancestors(event.pid);
+                }
+            }
         }
-        result
+        events
+    }
+
+    /// The cache of the processes of events, shared with the clones of the group. Only groups
+    /// built with `FanotifyBuilder::process_info` have one.
+    pub fn process_cache(&self) -> Option<MutexGuard<'_, ProcessCache>> {
+        let cache = self.processes.as_ref()?;
+        Some(cache.lock().unwrap_or_else(PoisonError::into_inner))
     }
 
     pub fn send_response<T: Into<i32>>(&self, fd: T, resp: FanotifyResponse) {
@@ -249,6 +1144,12 @@ impl Fanotify {
         }
     }
 
+    /// The configuration the group was registered with, or `None` if it was built from a raw
+    /// file descriptor.
+    pub fn config(&self) -> Option<&FanotifyBuilder> {
+        self.config.as_ref()
+    }
+
     pub fn as_raw_fd(&self) -> i32 {
         self.fd
     }
@@ -268,6 +1169,9 @@ impl Clone for Fanotify {
     fn clone(&self) -> Self {
         Self {
             fd: unsafe { libc::dup(self.fd) },
+            config: self.config,
+            marks: Arc::clone(&self.marks),
+            processes: self.processes.clone(),
         }
     }
 }
@@ -277,6 +1181,10 @@ pub struct FanotifyBuilder {
     class: FanotifyMode,
     flags: u32,
     event_flags: u32,
+    process_info: bool,
+    process_ancestry: bool,
+    stat_on_read: bool,
+    process_cache: ProcessCacheConfig,
 }
 
 impl FanotifyBuilder {
@@ -285,6 +1193,10 @@ impl FanotifyBuilder {
             class: FanotifyMode::NOTIF,
             flags: FAN_CLOEXEC,
             event_flags: O_CLOEXEC as u32,
+            process_info: false,
+            process_ancestry: false,
+            stat_on_read: false,
+            process_cache: ProcessCacheConfig::default(),
         }
     }
 
@@ -292,9 +1204,10 @@ impl FanotifyBuilder {
         Self { class, ..self }
     }
 
+    /// Adds raw `fanotify_init` flags to the ones already set, e.g. by `report_fid`.
     pub fn with_flags(self, flags: u32) -> Self {
         Self {
-            flags: FAN_CLOEXEC | flags,
+            flags: self.flags | flags,
             ..self
         }
     }
@@ -306,9 +1219,304 @@ impl FanotifyBuilder {
         }
     }
 
-    pub fn register(&self) -> Result<Fanotify, Error> {
+    fn with_flag(self, flag: u32) -> Self {
+        Self {
+            flags: self.flags | flag,
+            ..self
+        }
+    }
+
+    fn with_event_flag(self, flag: u32) -> Self {
+        Self {
+            event_flags: self.event_flags | flag,
+            ..self
+        }
+    }
+
+    /// Identify the object of each event by a file handle instead of a file descriptor
+    /// (`FAN_REPORT_FID`, since Linux 5.1).
+    pub fn report_fid(self) -> Self {
+        self.with_flag(FAN_REPORT_FID)
+    }
+
+    /// Identify the directory of each event by a file handle (`FAN_REPORT_DIR_FID`, since Linux 5.9).
+    pub fn report_dir_fid(self) -> Self {
+        self.with_flag(FAN_REPORT_DIR_FID)
+    }
+
+    /// Report the name of the directory entry along with the directory file handle
+    /// (`FAN_REPORT_NAME`, since Linux 5.9). Requires `report_dir_fid`.
+    pub fn report_name(self) -> Self {
+        self.with_flag(FAN_REPORT_NAME)
+    }
+
+    /// Report the thread id instead of the process id in `Event::pid` (`FAN_REPORT_TID`).
+    pub fn report_tid(self) -> Self {
+        self.with_flag(FAN_REPORT_TID)
+    }
+
+    /// Attach a pidfd of the process that caused each event (`FAN_REPORT_PIDFD`, since Linux 5.15).
+    pub fn report_pidfd(self) -> Self {
+        self.with_flag(FAN_REPORT_PIDFD)
+    }
+
+    /// Report the mount id of mount events (`FAN_REPORT_MNT`, since Linux 6.14). Required to
+    /// mark mount namespaces, and exclusive with the file id flags.
+    pub fn report_mnt(self) -> Self {
+        self.with_flag(FAN_REPORT_MNT)
+    }
+
+    /// Remove the limit of 16384 events in the queue (`FAN_UNLIMITED_QUEUE`).
+    pub fn unlimited_queue(self) -> Self {
+        self.with_flag(FAN_UNLIMITED_QUEUE)
+    }
+
+    /// Remove the limit of 8192 marks in the group (`FAN_UNLIMITED_MARKS`).
+    pub fn unlimited_marks(self) -> Self {
+        self.with_flag(FAN_UNLIMITED_MARKS)
+    }
+
+    /// Allow permission responses to request an audit record (`FAN_ENABLE_AUDIT`).
+    pub fn enable_audit(self) -> Self {
+        self.with_flag(FAN_ENABLE_AUDIT)
+    }
+
+    /// Make reads from the group non-blocking (`FAN_NONBLOCK`).
+    pub fn nonblocking(self) -> Self {
+        self.with_flag(FAN_NONBLOCK)
+    }
+
+    /// Set the access mode of the file descriptors opened for events.
+    pub fn with_event_access(self, access: EventAccess) -> Self {
+        Self {
+            event_flags: (self.event_flags & !(libc::O_ACCMODE as u32)) | access.to_open_flags(),
+            ..self
+        }
+    }
+
+    /// Open the file descriptors of events with `O_LARGEFILE`.
+    pub fn large_file(self) -> Self {
+        self.with_event_flag(O_LARGEFILE as u32)
+    }
+
+    /// Open the file descriptors of events with `O_NOATIME`.
+    pub fn no_atime(self) -> Self {
+        self.with_event_flag(O_NOATIME as u32)
+    }
+
+    /// Read the process of each event from `/proc` when reading events, see `Event::process`.
+    /// This is done by the library rather than the kernel. Processes are cached by the group
+    /// (see `with_process_cache`), so an event of a known process costs one file read.
+    /// Combine with `report_pidfd` to rule out pid reuse.
+    pub fn process_info(self) -> Self {
+        Self {
+            process_info: true,
+            ..self
+        }
+    }
+
+    /// Look up the ancestors of the process of each event as well, see `Event::ancestors`.
+    /// Enables `process_info`. Marking `FAN_OPEN_EXEC` events lets the group keep track of
+    /// the parents of processes that outlive them.
+    pub fn process_ancestry(self) -> Self {
+        Self {
+            process_info: true,
+            process_ancestry: true,
+            ..self
+        }
+    }
+
+    /// Read the metadata of the file of each event as soon as it is read, see `Event::stat`.
+    pub fn stat_on_read(self) -> Self {
+        Self {
+            stat_on_read: true,
+            ..self
+        }
+    }
+
+    /// Set the size and TTL of the process cache, and enable `process_info`.
+    pub fn with_process_cache(self, config: ProcessCacheConfig) -> Self {
+        Self {
+            process_info: true,
+            process_cache: config,
+            ..self
+        }
+    }
+
+    pub fn class(&self) -> FanotifyMode {
+        self.class
+    }
+
+    /// The flags passed to `fanotify_init`, including the class.
+    pub fn flags(&self) -> u32 {
+        self.init_flags()
+    }
+
+    /// The flags of the file descriptors opened for events.
+    pub fn event_flags(&self) -> u32 {
+        self.event_flags
+    }
+
+    pub fn has_flag(&self, flag: u32) -> bool {
+        self.init_flags() & flag == flag
+    }
+
+    /// Whether `process_info` was set.
+    pub fn has_process_info(&self) -> bool {
+        self.process_info
+    }
+
+    /// Whether `stat_on_read` was set.
+    pub fn has_stat_on_read(&self) -> bool {
+        self.stat_on_read
+    }
+
+    /// Whether `process_ancestry` was set.
+    pub fn has_process_ancestry(&self) -> bool {
+        self.process_ancestry
+    }
+
+    pub fn process_cache(&self) -> ProcessCacheConfig {
+        self.process_cache
+    }
+
+    pub fn register(&self) -> Result<Fanotify, FanotifyError> {
+        let flags = self.init_flags();
+        if let Err(reason) = self.validate() {
+            return Err(FanotifyError::InvalidCombination {
+                call: Call::Init {
+                    flags,
+                    event_f_flags: self.event_flags,
+                },
+                reason,
+            });
+        }
         Ok(Fanotify {
-            fd: fanotify_init(self.flags | self.class.to_fan_class(), self.event_flags)?,
+            fd: init(flags, self.event_flags)?,
+            config: Some(*self),
+            marks: Arc::default(),
+            processes: self
+                .process_info
+                .then(|| Arc::new(Mutex::new(ProcessCache::new(self.process_cache)))),
         })
     }
+
+    fn init_flags(&self) -> u32 {
+        self.flags | self.class.to_fan_class()
+    }
+
+    fn is_notif(&self) -> bool {
+        self.init_flags() & (FAN_CLASS_CONTENT | FAN_CLASS_PRE_CONTENT) == 0
+    }
+
+    /// Checks the flags against the combinations `fanotify_init(2)` refuses.
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.flags & FAN_REPORT_NAME != 0 && self.flags & FAN_REPORT_DIR_FID == 0 {
+            return Err("FAN_REPORT_NAME requires FAN_REPORT_DIR_FID");
+        }
+        if self.flags & FAN_REPORT_MNT != 0
+            && self.flags & (FAN_REPORT_FID | FAN_REPORT_DIR_FID) != 0
+        {
+            return Err(
+                "FAN_REPORT_MNT can't be combined with FAN_REPORT_FID or FAN_REPORT_DIR_FID",
+            );
+        }
+        if self.flags & FAN_REPORT_PIDFD != 0 && self.flags & FAN_REPORT_TID != 0 {
+            return Err("FAN_REPORT_PIDFD can't be combined with FAN_REPORT_TID");
+        }
+        Ok(())
+    }
+}
+
+/// Adds `FAN_MARK_IGNORED_SURV_MODIFY` to an ignore mask added with `FAN_MARK_IGNORE` on a
+/// directory, mount or filesystem, which the kernel refuses without it. See
+/// `IgnoreOptions::ondir`.
+fn implied_ignore_flags(flags: u32, dirfd: i32, path: Option<&OsStr>) -> u32 {
+    if flags & (FAN_MARK_ADD | FAN_MARK_IGNORE) != FAN_MARK_ADD | FAN_MARK_IGNORE
+        || flags & FAN_MARK_IGNORED_SURV_MODIFY != 0
+    {
+        return flags;
+    }
+    let follow = flags & FAN_MARK_DONT_FOLLOW == 0;
+    if flags & (FAN_MARK_MOUNT | FAN_MARK_FILESYSTEM) != 0 || is_dir_at(dirfd, path, follow) {
+        flags | FAN_MARK_IGNORED_SURV_MODIFY
+    } else {
+        flags
+    }
+}
+
+/// Whether `path` relative to `dirfd`, or `dirfd` itself if `path` is `None`, is a directory.
+pub(crate) fn is_dir_at(dirfd: i32, path: Option<&OsStr>, follow: bool) -> bool {
+    let Ok(path) = CString::new(path.map_or(&[][..], OsStr::as_bytes)) else {
+        return false;
+    };
+    let mut flags = if follow { 0 } else { libc::AT_SYMLINK_NOFOLLOW };
+    if path.as_bytes().is_empty() {
+        flags |= libc::AT_EMPTY_PATH;
+    }
+    let mut stat = MaybeUninit::<libc::stat>::zeroed();
+    if unsafe { libc::fstatat(dirfd, path.as_ptr(), stat.as_mut_ptr(), flags) } != 0 {
+        return false;
+    }
+    unsafe { stat.assume_init() }.st_mode & libc::S_IFMT == libc::S_IFDIR
+}
+
+/// Where `fanotify_mark` finds the object of `mark`: its path, or the namespace it was added
+/// with for mount namespace marks.
+fn mark_location(mark: &Mark, flags: u32) -> Result<(i32, Option<&OsStr>), FanotifyError> {
+    mark.location().ok_or(FanotifyError::UnreachableMark {
+        call: Call::Mark {
+            flags,
+            mask: mark.mask | mark.ignored_mask,
+            path: None,
+        },
+    })
+}
+
+/// Checks a `fanotify_mark` call against the combinations `fanotify_mark(2)` refuses.
+///
+/// Checks depending on the group's class are skipped when its configuration is unknown,
+/// i.e. when the `Fanotify` was built from a raw file descriptor.
+fn validate_mark(
+    config: Option<&FanotifyBuilder>,
+    flags: u32,
+    mask: u64,
+) -> Result<(), &'static str> {
+    if flags & FAN_MARK_FLUSH != 0 {
+        return match flags & !FAN_MARK_FLUSH {
+            0 | FAN_MARK_MOUNT | FAN_MARK_FILESYSTEM | FAN_MARK_MNTNS => Ok(()),
+            _ => Err("FAN_MARK_FLUSH can only be combined with one of FAN_MARK_MOUNT, FAN_MARK_FILESYSTEM or FAN_MARK_MNTNS"),
+        };
+    }
+    let mount_events = FAN_MNT_ATTACH | FAN_MNT_DETACH;
+    if flags & FAN_MARK_MNTNS == FAN_MARK_MNTNS {
+        if mask & !mount_events != 0 {
+            return Err("FAN_MARK_MNTNS only accepts FAN_MNT_ATTACH and FAN_MNT_DETACH");
+        }
+        if config.is_some_and(|config| !config.has_flag(FAN_REPORT_MNT)) {
+            return Err("FAN_MARK_MNTNS requires a group initialized with FAN_REPORT_MNT");
+        }
+    } else if mask & mount_events != 0 {
+        return Err("FAN_MNT_ATTACH and FAN_MNT_DETACH require FAN_MARK_MNTNS");
+    }
+    if flags & FAN_MARK_EVICTABLE != 0 && flags & (FAN_MARK_MOUNT | FAN_MARK_FILESYSTEM) != 0 {
+        return Err("FAN_MARK_EVICTABLE can only be used with inode marks");
+    }
+    let perm_events = FAN_OPEN_PERM | FAN_ACCESS_PERM | FAN_OPEN_EXEC_PERM;
+    if mask & perm_events != 0 && config.is_some_and(FanotifyBuilder::is_notif) {
+        return Err("permission events require a FAN_CLASS_CONTENT or FAN_CLASS_PRE_CONTENT group");
+    }
+    let inode_events =
+        FAN_CREATE | FAN_DELETE | FAN_MOVE | FAN_ATTRIB | FAN_DELETE_SELF | FAN_MOVE_SELF;
+    if mask & inode_events != 0 && flags & (FAN_MARK_MOUNT | FAN_MARK_FILESYSTEM) == FAN_MARK_MOUNT
+    {
+        return Err("inode events (FAN_CREATE, FAN_DELETE, FAN_MOVE, FAN_ATTRIB, FAN_DELETE_SELF, FAN_MOVE_SELF) can't be used with FAN_MARK_MOUNT");
+    }
+    Ok(())
+}
+
+impl Default for FanotifyBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
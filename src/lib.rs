@@ -1,5 +1,17 @@
+#[cfg(feature = "config")]
+pub mod config;
+pub mod container;
+pub mod error;
+pub mod exec;
+pub mod fdinfo;
 pub mod high_level;
 pub mod low_level;
+pub mod mark_set;
+pub mod mountinfo;
+pub mod process;
+pub mod registry;
+
+pub use error::FanotifyError;
 
 pub trait FanotifyPath {
     fn as_os_str(&self) -> &std::ffi::OsStr;
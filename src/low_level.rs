@@ -3,7 +3,7 @@ use libc::{__s32, __u16, __u32, __u64, __u8};
 use std::io::Error;
 use std::mem;
 use std::os::unix::ffi::OsStrExt;
-use std::slice;
+use std::ptr;
 
 #[doc(hidden)]
 /// Re-export relevant libc constants
@@ -47,6 +47,39 @@ pub struct FanotifyEventMetadata {
     pub pid: __s32,
 }
 
+/// Header of an information record following the metadata of an event, in groups
+/// initialized with `FAN_REPORT_FID`, `FAN_REPORT_PIDFD`, `FAN_REPORT_MNT` and similar flags.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct FanotifyEventInfoHeader {
+    /// One of the `FAN_EVENT_INFO_TYPE_*` values.
+    pub info_type: __u8,
+    pub pad: __u8,
+    /// Length of the record, including this header.
+    pub len: __u16,
+}
+
+/// An information record of an event, as returned by `fanotify_read_info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FanotifyEventInfo {
+    /// One of the `FAN_EVENT_INFO_TYPE_*` values.
+    pub info_type: u8,
+    /// The record, without its header.
+    pub data: Vec<u8>,
+}
+
+impl FanotifyEventInfo {
+    /// The file descriptor held by a `FAN_EVENT_INFO_TYPE_PIDFD` record, if it is one and
+    /// the kernel could open one (i.e. not `FAN_NOPIDFD` or `FAN_EPIDFD`).
+    pub fn pidfd(&self) -> Option<i32> {
+        if self.info_type != FAN_EVENT_INFO_TYPE_PIDFD {
+            return None;
+        }
+        let pidfd = __s32::from_ne_bytes(self.data.get(..4)?.try_into().ok()?);
+        Some(pidfd).filter(|pidfd| *pidfd >= 0)
+    }
+}
+
 #[derive(Debug)]
 #[repr(C)]
 /// It is used to control file access.
@@ -71,6 +104,10 @@ pub const FAN_DENY: u32 = 0x02;
 pub const FAN_AUDIT: u32 = 0x10;
 /// Indicates a queue overflow.
 pub const FAN_NOFD: i32 = -1;
+/// Value of the pidfd info record when the process that caused the event already exited.
+pub const FAN_NOPIDFD: i32 = FAN_NOFD;
+/// Value of the pidfd info record when creating the pidfd failed.
+pub const FAN_EPIDFD: i32 = -2;
 /// The event queue exceeded the limit of 16384 entries.
 ///
 ///
@@ -123,6 +160,12 @@ pub const FAN_REPORT_DIR_FID: u32 = 0x0000_0400;
 /// Flags to determine fanotify event format
 /// report events with name
 pub const FAN_REPORT_NAME: u32 = 0x0000_0800;
+/// Flags to determine fanotify event format
+/// report a pidfd of the process that caused the event (since Linux 5.15)
+pub const FAN_REPORT_PIDFD: u32 = 0x0000_0080;
+/// Flags to determine fanotify event format
+/// report the mount id of mount events (since Linux 6.14). Can't be combined with `FAN_REPORT_FID` and the other file id flags.
+pub const FAN_REPORT_MNT: u32 = 0x0000_4000;
 /// Create an event when a file or directory is accessed (read).
 pub const FAN_ACCESS: u64 = 0x0000_0001;
 /// Create an event when a file is modified (write).
@@ -160,6 +203,12 @@ pub const FAN_ACCESS_PERM: u64 = 0x0002_0000;
 /// Create an event when a permission to open a file for exec is requested. <br/>
 /// An fanotify file descriptor created with `FAN_CLASS_PRE_CONTENT` or `FAN_CLASS_CONTENT` is required.
 pub const FAN_OPEN_EXEC_PERM: u64 = 0x0004_0000;
+/// Create an event when a mount is attached to a marked mount namespace (since Linux 6.14). <br/>
+/// Requires a `FAN_MARK_MNTNS` mark and a group initialized with `FAN_REPORT_MNT`.
+pub const FAN_MNT_ATTACH: u64 = 0x0100_0000;
+/// Create an event when a mount is detached from a marked mount namespace (since Linux 6.14). <br/>
+/// Requires a `FAN_MARK_MNTNS` mark and a group initialized with `FAN_REPORT_MNT`.
+pub const FAN_MNT_DETACH: u64 = 0x0200_0000;
 /// Create events for directories—for example, when `opendir(3)`, `readdir(3)` (but see BUGS), and `closedir(3)` are called. <br/>
 /// Without this flag, events are created only for files. <br/>
 /// In the context of directory entry events, such as `FAN_CREATE,FAN_DELETE`, `FAN_MOVED_FROM`, and `FAN_MOVED_TO`, specifying the flag `FAN_ONDIR` is required in order to create events when subdirectory entries are modified (i.e., `mkdir(2)`/`rmdir(2)`).
@@ -202,11 +251,38 @@ pub const FAN_MARK_MOUNT: u32 = 0x0000_0010;
 /// The filesystem containing pathname will be marked.  <br/>
 /// All the contained files and directories of the filesystem from any mount point will be monitored.
 pub const FAN_MARK_FILESYSTEM: u32 = 0x0000_0100;
+/// Mark the mount namespace specified by `dirfd`, a file descriptor of `/proc/<pid>/ns/mnt` (since Linux 6.14).  <br/>
+/// The mask may only contain `FAN_MNT_ATTACH` and `FAN_MNT_DETACH`.
+pub const FAN_MARK_MNTNS: u32 = 0x0000_0110;
 /// The events in mask shall be added to or removed from the ignore mask.
 pub const FAN_MARK_IGNORED_MASK: u32 = 0x0000_0020;
 /// The ignore mask shall survive modify events.  <br/>
 /// If this flag is not set, the ignore mask is cleared when a modify event occurs for the ignored file or directory.
 pub const FAN_MARK_IGNORED_SURV_MODIFY: u32 = 0x0000_0040;
+/// The events in mask shall be added to or removed from the ignore mask (since Linux 6.0).  <br/>
+/// Unlike `FAN_MARK_IGNORED_MASK`, the ignore mask applies to events on directories only if `FAN_ONDIR` is set in mask,
+/// and to events on children of a directory only if `FAN_EVENT_ON_CHILD` is set in mask.
+pub const FAN_MARK_IGNORE: u32 = 0x0000_0400;
+/// The inode mark may be evicted from memory along with the inode when it is no longer in use (since Linux 5.19).  <br/>
+/// This is useful for ignore masks that cache a decision, as they don't pin the inode.
+/// It can't be used with `FAN_MARK_MOUNT` or `FAN_MARK_FILESYSTEM`, and fails with `EEXIST` if a non-evictable mark exists for the inode.
+pub const FAN_MARK_EVICTABLE: u32 = 0x0000_0200;
+/// `FAN_MARK_IGNORE` with an ignore mask that survives modify events.
+pub const FAN_MARK_IGNORE_SURV: u32 = FAN_MARK_IGNORE | FAN_MARK_IGNORED_SURV_MODIFY;
+/// Information record carrying a file handle of the object of the event, see `FAN_REPORT_FID`.
+pub const FAN_EVENT_INFO_TYPE_FID: u8 = 1;
+/// Information record carrying a file handle of the directory and the name of the entry, see `FAN_REPORT_NAME`.
+pub const FAN_EVENT_INFO_TYPE_DFID_NAME: u8 = 2;
+/// Information record carrying a file handle of the directory, see `FAN_REPORT_DIR_FID`.
+pub const FAN_EVENT_INFO_TYPE_DFID: u8 = 3;
+/// Information record carrying a pidfd of the process that caused the event, see `FAN_REPORT_PIDFD`.
+pub const FAN_EVENT_INFO_TYPE_PIDFD: u8 = 4;
+/// Information record carrying the error of a `FAN_FS_ERROR` event.
+pub const FAN_EVENT_INFO_TYPE_ERROR: u8 = 5;
+/// Information record carrying the range of a pre-content event.
+pub const FAN_EVENT_INFO_TYPE_RANGE: u8 = 6;
+/// Information record carrying the 64-bit mount id of a mount event, see `FAN_REPORT_MNT`.
+pub const FAN_EVENT_INFO_TYPE_MNT: u8 = 7;
 pub const AT_FDCWD: i32 = -100;
 pub const AT_SYMLINK_NOFOLLOW: i32 = 0x100;
 pub const AT_REMOVEDIR: i32 = 0x200;
@@ -293,6 +369,8 @@ pub fn fanotify_init(flags: u32, event_f_flags: u32) -> Result<i32, Error> {
 /// * `FAN_MARK_FILESYSTEM` (since Linux 4.20)
 /// * `FAN_MARK_IGNORED_MASK`
 /// * `FAN_MARK_IGNORED_SURV_MODIFY`
+/// * `FAN_MARK_IGNORE` (since Linux 6.0)
+/// * `FAN_MARK_EVICTABLE` (since Linux 5.19)
 ///
 ///
 /// `mask` defines which events shall be listened for (or which shall be ignored).  It is a bit mask composed of the following values:
@@ -361,7 +439,49 @@ pub fn fanotify_mark<P: ?Sized + FanotifyPath>(
     }
 }
 
+/// Same as `fanotify_mark`, but passes a `NULL` pathname so the filesystem object to be marked is
+/// the one referred to by `dirfd` itself.
+///
+/// Marking an already opened file descriptor is not subject to the races of resolving a pathname.
+/// # Examples
+/// ```
+/// use fanotify::low_level::*;
+/// use std::os::fd::AsRawFd;
+/// let fd = fanotify_init(FAN_CLASS_NOTIF, O_RDONLY as u32).unwrap();
+/// let dir = std::fs::File::open("./").unwrap();
+/// fanotify_mark_fd(fd, FAN_MARK_ADD, FAN_OPEN | FAN_CLOSE, dir.as_raw_fd()).unwrap();
+/// ```
+pub fn fanotify_mark_fd(
+    fanotify_fd: i32,
+    flags: u32,
+    mask: u64,
+    dirfd: i32,
+) -> Result<(), Error> {
+    unsafe {
+        match libc::fanotify_mark(fanotify_fd, flags, mask, dirfd, std::ptr::null()) {
+            0 => Ok(()),
+            _ => Err(Error::last_os_error()),
+        }
+    }
+}
+
 pub fn fanotify_read(fanotify_fd: i32) -> Vec<FanotifyEventMetadata> {
+    fanotify_read_info(fanotify_fd)
+        .into_iter()
+        .map(|(metadata, info)| {
+            // The records are dropped, don't leak the pidfds they hold.
+            info.iter().filter_map(FanotifyEventInfo::pidfd).for_each(close_fd);
+            metadata
+        })
+        .collect()
+}
+
+/// Same as `fanotify_read`, but also returns the information records following the metadata of
+/// each event.
+///
+/// Records of type `FAN_EVENT_INFO_TYPE_PIDFD` hold a file descriptor, which the caller is
+/// responsible for closing.
+pub fn fanotify_read_info(fanotify_fd: i32) -> Vec<(FanotifyEventMetadata, Vec<FanotifyEventInfo>)> {
     let mut vec = Vec::new();
     let mut buffer = Box::new([0u8;FAN_EVENT_METADATA_LEN * 200]);
     unsafe {
@@ -369,16 +489,46 @@ pub fn fanotify_read(fanotify_fd: i32) -> Vec<FanotifyEventMetadata> {
         
         let sizeof = libc::read(fanotify_fd, buffer.as_mut_ptr() as _, FAN_EVENT_METADATA_LEN * 200);
         if sizeof != libc::EAGAIN as isize && sizeof > 0 {
-            let src = slice::from_raw_parts(
-                buffer.as_ptr().cast::<FanotifyEventMetadata>(),
-                sizeof as usize / FAN_EVENT_METADATA_LEN,
-            );
-            vec.extend_from_slice(src);
+            // Events are variable-length when the group reports file ids or other info records,
+            // so walk the buffer using `event_len` instead of the size of the metadata.
+            let mut offset = 0;
+            while offset + FAN_EVENT_METADATA_LEN <= sizeof as usize {
+                let metadata = ptr::read_unaligned(
+                    buffer.as_ptr().add(offset).cast::<FanotifyEventMetadata>(),
+                );
+                let event_len = metadata.event_len as usize;
+                if event_len < FAN_EVENT_METADATA_LEN || offset + event_len > sizeof as usize {
+                    break;
+                }
+                let records = buffer
+                    .get(offset + metadata.metadata_len as usize..offset + event_len)
+                    .unwrap_or_default();
+                vec.push((metadata, parse_info(records)));
+                offset += event_len;
+            }
         }
     }
     vec
 }
 
+/// Splits the information records of an event.
+fn parse_info(mut records: &[u8]) -> Vec<FanotifyEventInfo> {
+    let mut info = Vec::new();
+    while records.len() >= mem::size_of::<FanotifyEventInfoHeader>() {
+        let header = unsafe { ptr::read_unaligned(records.as_ptr().cast::<FanotifyEventInfoHeader>()) };
+        let len = header.len as usize;
+        if len < mem::size_of::<FanotifyEventInfoHeader>() || len > records.len() {
+            break;
+        }
+        info.push(FanotifyEventInfo {
+            info_type: header.info_type,
+            data: records[mem::size_of::<FanotifyEventInfoHeader>()..len].to_vec(),
+        });
+        records = &records[len..];
+    }
+    info
+}
+
 pub fn close_fd(fd: i32) {
     unsafe {
         libc::close(fd);
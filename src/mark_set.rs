@@ -0,0 +1,327 @@
+use crate::error::FanotifyError;
+use crate::high_level::{unignore_flags, Fanotify, IgnoreOptions};
+use crate::low_level::{AT_FDCWD, FAN_MARK_ADD, FAN_MARK_REMOVE};
+use crate::registry::{MarkKind, MarkTarget};
+use crate::FanotifyPath;
+use std::fmt;
+use std::path::PathBuf;
+
+/// A mark operation of a `MarkSet`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkOp {
+    /// Add `mask` to the mark of `kind` on `path`.
+    Add {
+        kind: MarkKind,
+        mask: u64,
+        path: PathBuf,
+    },
+    /// Add `mask` to the ignore mask of the mark of `kind` on `path`.
+    Ignore {
+        kind: MarkKind,
+        mask: u64,
+        path: PathBuf,
+        options: IgnoreOptions,
+    },
+    /// Remove `mask` from the mark of `kind` on `path`.
+    Remove {
+        kind: MarkKind,
+        mask: u64,
+        path: PathBuf,
+    },
+    /// Remove `mask` from the ignore mask of the mark of `kind` on `path`.
+    Unignore {
+        kind: MarkKind,
+        mask: u64,
+        path: PathBuf,
+    },
+}
+
+impl MarkOp {
+    fn kind(&self) -> MarkKind {
+        match self {
+            MarkOp::Add { kind, .. }
+            | MarkOp::Ignore { kind, .. }
+            | MarkOp::Remove { kind, .. }
+            | MarkOp::Unignore { kind, .. } => *kind,
+        }
+    }
+
+    fn path(&self) -> &PathBuf {
+        match self {
+            MarkOp::Add { path, .. }
+            | MarkOp::Ignore { path, .. }
+            | MarkOp::Remove { path, .. }
+            | MarkOp::Unignore { path, .. } => path,
+        }
+    }
+
+    fn is_ignore(&self) -> bool {
+        matches!(self, MarkOp::Ignore { .. } | MarkOp::Unignore { .. })
+    }
+
+    fn is_remove(&self) -> bool {
+        matches!(self, MarkOp::Remove { .. } | MarkOp::Unignore { .. })
+    }
+
+    /// The flags and mask passed to `fanotify_mark` to apply the operation.
+    fn to_mark(&self) -> (u32, u64) {
+        match self {
+            MarkOp::Add { kind, mask, .. } => (FAN_MARK_ADD | kind.to_mark_flags(), *mask),
+            MarkOp::Ignore {
+                kind,
+                mask,
+                options,
+                ..
+            } => (
+                FAN_MARK_ADD | kind.to_mark_flags() | options.to_mark_flags(),
+                options.to_mask(*mask),
+            ),
+            MarkOp::Remove { kind, mask, .. } => (FAN_MARK_REMOVE | kind.to_mark_flags(), *mask),
+            MarkOp::Unignore { kind, mask, .. } => (
+                FAN_MARK_REMOVE | kind.to_mark_flags() | unignore_flags(*mask),
+                *mask,
+            ),
+        }
+    }
+
+    /// The operation undoing this one, for the events it `changed`. Ignore masks removed by
+    /// `Unignore` are restored with the `options` they had.
+    fn inverse(&self, changed: u64, options: IgnoreOptions) -> MarkOp {
+        let (kind, path) = (self.kind(), self.path().clone());
+        match self {
+            MarkOp::Add { .. } => MarkOp::Remove {
+                kind,
+                mask: changed,
+                path,
+            },
+            MarkOp::Ignore { .. } => MarkOp::Unignore {
+                kind,
+                mask: changed,
+                path,
+            },
+            MarkOp::Remove { .. } => MarkOp::Add {
+                kind,
+                mask: changed,
+                path,
+            },
+            MarkOp::Unignore { .. } => MarkOp::Ignore {
+                kind,
+                mask: changed,
+                path,
+                options,
+            },
+        }
+    }
+}
+
+/// A batch of mark operations applied to a group as a whole.
+///
+/// `MarkSet::apply` stops at the first operation the kernel refuses, and undoes the operations
+/// applied before it, so the group is left as it was.
+///
+/// ```
+/// use fanotify::high_level::*;
+/// use fanotify::mark_set::MarkSet;
+/// let ft = Fanotify::new_nonblocking(FanotifyMode::NOTIF).unwrap();
+/// let err = MarkSet::new()
+///     .add_path(FAN_OPEN, "/tmp")
+///     .add_path(FAN_OPEN, "/tmp/fanotify_mark_set_doc_missing")
+///     .apply(&ft)
+///     .unwrap_err();
+/// assert_eq!(err.index, 1);
+/// assert!(ft.marks().is_empty());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MarkSet {
+    ops: Vec<MarkOp>,
+}
+
+impl MarkSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_op(mut self, op: MarkOp) -> Self {
+        self.ops.push(op);
+        self
+    }
+
+    fn add<P: ?Sized + FanotifyPath>(self, kind: MarkKind, mode: u64, path: &P) -> Self {
+        self.with_op(MarkOp::Add {
+            kind,
+            mask: mode,
+            path: PathBuf::from(path.as_os_str()),
+        })
+    }
+
+    fn ignore<P: ?Sized + FanotifyPath>(
+        self,
+        kind: MarkKind,
+        mode: u64,
+        path: &P,
+        options: IgnoreOptions,
+    ) -> Self {
+        self.with_op(MarkOp::Ignore {
+            kind,
+            mask: mode,
+            path: PathBuf::from(path.as_os_str()),
+            options,
+        })
+    }
+
+    pub fn add_path<P: ?Sized + FanotifyPath>(self, mode: u64, path: &P) -> Self {
+        self.add(MarkKind::Inode, mode, path)
+    }
+
+    pub fn add_mountpoint<P: ?Sized + FanotifyPath>(self, mode: u64, path: &P) -> Self {
+        self.add(MarkKind::Mount, mode, path)
+    }
+
+    pub fn add_filesystem<P: ?Sized + FanotifyPath>(self, mode: u64, path: &P) -> Self {
+        self.add(MarkKind::Filesystem, mode, path)
+    }
+
+    pub fn ignore_path<P: ?Sized + FanotifyPath>(
+        self,
+        mode: u64,
+        path: &P,
+        options: IgnoreOptions,
+    ) -> Self {
+        self.ignore(MarkKind::Inode, mode, path, options)
+    }
+
+    pub fn ignore_mountpoint<P: ?Sized + FanotifyPath>(
+        self,
+        mode: u64,
+        path: &P,
+        options: IgnoreOptions,
+    ) -> Self {
+        self.ignore(MarkKind::Mount, mode, path, options)
+    }
+
+    pub fn ignore_filesystem<P: ?Sized + FanotifyPath>(
+        self,
+        mode: u64,
+        path: &P,
+        options: IgnoreOptions,
+    ) -> Self {
+        self.ignore(MarkKind::Filesystem, mode, path, options)
+    }
+
+    pub fn remove_path<P: ?Sized + FanotifyPath>(self, mode: u64, path: &P) -> Self {
+        self.with_op(MarkOp::Remove {
+            kind: MarkKind::Inode,
+            mask: mode,
+            path: PathBuf::from(path.as_os_str()),
+        })
+    }
+
+    pub fn unignore_path<P: ?Sized + FanotifyPath>(self, mode: u64, path: &P) -> Self {
+        self.with_op(MarkOp::Unignore {
+            kind: MarkKind::Inode,
+            mask: mode,
+            path: PathBuf::from(path.as_os_str()),
+        })
+    }
+
+    pub fn ops(&self) -> &[MarkOp] {
+        &self.ops
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Applies the operations in order. On the first failure, the operations already applied
+    /// are undone, in reverse order.
+    ///
+    /// Only the events an operation actually changed are rolled back: events a mark already had
+    /// before an `Add`, or didn't have before a `Remove`, are left untouched. This relies on the
+    /// mark registry of the group.
+    pub fn apply(&self, fanotify: &Fanotify) -> Result<(), MarkSetError> {
+        let mut undo: Vec<MarkOp> = Vec::new();
+        for (index, op) in self.ops.iter().enumerate() {
+            let (flags, mask) = op.to_mark();
+            let existing = existing_mark(fanotify, op);
+            let (existing_mask, options) = existing.unwrap_or_default();
+            let changed = if op.is_remove() {
+                mask & existing_mask
+            } else {
+                mask & !existing_mask
+            };
+            if let Err(source) =
+                fanotify.mark_at(flags, mask, AT_FDCWD, Some(op.path().as_os_str()))
+            {
+                let rollback_errors = undo
+                    .iter()
+                    .rev()
+                    .filter_map(|op| apply_op(fanotify, op).err())
+                    .collect();
+                return Err(MarkSetError {
+                    index,
+                    source,
+                    rollback_errors,
+                });
+            }
+            if changed != 0 {
+                undo.push(op.inverse(changed, options));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The events the group already has in the mask (or ignore mask) `op` modifies, along with
+/// the options of its ignore mask.
+fn existing_mark(fanotify: &Fanotify, op: &MarkOp) -> Option<(u64, IgnoreOptions)> {
+    let target = MarkTarget::resolve(AT_FDCWD, Some(op.path().as_os_str()), true).ok()?;
+    let registry = fanotify.registry();
+    let mark = registry.find(op.kind(), &target)?;
+    if op.is_ignore() {
+        Some((mark.ignored_mask, mark.ignore_options))
+    } else {
+        Some((mark.mask, mark.ignore_options))
+    }
+}
+
+fn apply_op(fanotify: &Fanotify, op: &MarkOp) -> Result<(), FanotifyError> {
+    let (flags, mask) = op.to_mark();
+    fanotify.mark_at(flags, mask, AT_FDCWD, Some(op.path().as_os_str()))
+}
+
+/// The error returned by `MarkSet::apply`.
+#[derive(Debug)]
+pub struct MarkSetError {
+    /// Index of the operation that failed in `MarkSet::ops`.
+    pub index: usize,
+    pub source: FanotifyError,
+    /// Errors met while rolling back the operations applied before the failure. If not empty,
+    /// the group may still have some of the marks of the batch.
+    pub rollback_errors: Vec<FanotifyError>,
+}
+
+impl fmt::Display for MarkSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mark operation {} failed: {}", self.index, self.source)?;
+        if !self.rollback_errors.is_empty() {
+            write!(
+                f,
+                " ({} operations could not be rolled back)",
+                self.rollback_errors.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MarkSetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<MarkSetError> for std::io::Error {
+    fn from(err: MarkSetError) -> std::io::Error {
+        std::io::Error::other(err)
+    }
+}
@@ -0,0 +1,588 @@
+use crate::error::FanotifyError;
+use crate::high_level::{is_unlinked, strip_deleted, Fanotify};
+use crate::low_level::AT_FDCWD;
+use crate::mark_set::{MarkSet, MarkSetError};
+use crate::registry::{MarkKind, MarkTarget};
+use crate::FanotifyPath;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{canonicalize, read_link, File};
+use std::io::{Error, Read, Seek, SeekFrom};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+use std::path::{Path, PathBuf};
+
+/// A mount, as described by a line of `/proc/<pid>/mountinfo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountInfo {
+    /// Unique id of the mount, as reported by `statx` in `stx_mnt_id`.
+    pub mount_id: u64,
+    pub parent_id: u64,
+    /// Device of the filesystem, encoded like `st_dev`.
+    pub dev: u64,
+    /// Path of the directory of the filesystem that forms the root of the mount.
+    pub root: PathBuf,
+    /// Where the mount is, relative to the root of the process.
+    pub mount_point: PathBuf,
+    /// Per-mount options, e.g. `rw,relatime`.
+    pub options: String,
+    /// Filesystem type, e.g. `ext4` or `tmpfs`.
+    pub fstype: String,
+    /// Filesystem specific source, e.g. `/dev/sda1`, or `none`.
+    pub source: String,
+    /// Per-superblock options.
+    pub super_options: String,
+}
+
+/// Reads the mount table of the calling process.
+pub fn read() -> Result<Vec<MountInfo>, Error> {
+    Ok(parse(&std::fs::read_to_string("/proc/self/mountinfo")?))
+}
+
+/// Parses the contents of a `mountinfo` file. Malformed lines are skipped.
+///
+/// ```
+/// let mounts = fanotify::mountinfo::parse(
+///     "36 35 98:0 /mnt1 /mnt/my\\040disk rw,noatime master:1 - ext3 /dev/root rw\n",
+/// );
+/// assert_eq!(mounts[0].mount_id, 36);
+/// assert_eq!(mounts[0].mount_point, std::path::Path::new("/mnt/my disk"));
+/// assert_eq!(mounts[0].fstype, "ext3");
+/// ```
+pub fn parse(contents: &str) -> Vec<MountInfo> {
+    contents.lines().filter_map(parse_line).collect()
+}
+
+fn parse_line(line: &str) -> Option<MountInfo> {
+    let (fields, fs_fields) = line.split_once(" - ")?;
+    let mut fields = fields.split(' ');
+    let mount_id = fields.next()?.parse().ok()?;
+    let parent_id = fields.next()?.parse().ok()?;
+    let (major, minor) = fields.next()?.split_once(':')?;
+    let root = unescape(fields.next()?);
+    let mount_point = unescape(fields.next()?);
+    let options = fields.next()?.to_string();
+    let mut fs_fields = fs_fields.split(' ');
+    Some(MountInfo {
+        mount_id,
+        parent_id,
+        dev: libc::makedev(major.parse().ok()?, minor.parse().ok()?),
+        root: PathBuf::from(root),
+        mount_point: PathBuf::from(mount_point),
+        options,
+        fstype: fs_fields.next()?.to_string(),
+        source: unescape(fs_fields.next()?),
+        super_options: fs_fields.next().unwrap_or_default().to_string(),
+    })
+}
+
+/// Undoes the octal escaping of spaces, tabs, newlines and backslashes done by the kernel.
+fn unescape(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(index) = rest.find('\\') {
+        unescaped.push_str(&rest[..index]);
+        rest = &rest[index..];
+        match rest
+            .get(1..4)
+            .and_then(|octal| u8::from_str_radix(octal, 8).ok())
+        {
+            Some(byte) => {
+                unescaped.push(byte as char);
+                rest = &rest[4..];
+            }
+            None => {
+                unescaped.push('\\');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Kernel interfaces mounted as filesystems, which hold no user files worth watching.
+/// `MountFilter` skips them unless asked for by type.
+pub const PSEUDO_FILESYSTEMS: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "proc",
+    "pstore",
+    "rpc_pipefs",
+    "securityfs",
+    "selinuxfs",
+    "sysfs",
+    "tracefs",
+];
+
+/// Selects mounts by filesystem type, mount point and source. A mount matches if it matches
+/// each criterion that was set, and any of the values given for it.
+///
+/// Unless filesystem types are given with `fstype`, mounts of the `PSEUDO_FILESYSTEMS` are
+/// excluded. See `exclude_fstype` and `include_pseudo`.
+///
+/// ```
+/// use fanotify::mountinfo::{parse, MountFilter};
+/// let mounts = parse(
+///     "40 28 8:17 / /media/usb rw - vfat /dev/sdb1 rw\n\
+///      23 28 0:22 / /proc rw - proc proc rw\n",
+/// );
+/// let filter = MountFilter::new().fstype("vfat").path_prefix("/media");
+/// assert!(filter.matches(&mounts[0]));
+/// assert!(!filter.source("/dev/nvme").matches(&mounts[0]));
+/// assert!(!MountFilter::new().matches(&mounts[1]));
+/// assert!(MountFilter::new().include_pseudo().matches(&mounts[1]));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountFilter {
+    fstypes: Vec<String>,
+    excluded_fstypes: Vec<String>,
+    path_prefixes: Vec<PathBuf>,
+    sources: Vec<String>,
+}
+
+impl Default for MountFilter {
+    fn default() -> Self {
+        Self {
+            fstypes: Vec::new(),
+            excluded_fstypes: PSEUDO_FILESYSTEMS
+                .iter()
+                .map(|fstype| fstype.to_string())
+                .collect(),
+            path_prefixes: Vec::new(),
+            sources: Vec::new(),
+        }
+    }
+}
+
+impl MountFilter {
+    /// A filter matching every mount but the ones of pseudo-filesystems.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match mounts of filesystem type `fstype`. This overrides the exclusions.
+    pub fn fstype(mut self, fstype: &str) -> Self {
+        self.fstypes.push(fstype.to_string());
+        self
+    }
+
+    /// Skip mounts of filesystem type `fstype`, unless it is given to `fstype`.
+    pub fn exclude_fstype(mut self, fstype: &str) -> Self {
+        self.excluded_fstypes.push(fstype.to_string());
+        self
+    }
+
+    /// Stop excluding the `PSEUDO_FILESYSTEMS`, and the types given to `exclude_fstype`.
+    pub fn include_pseudo(mut self) -> Self {
+        self.excluded_fstypes.clear();
+        self
+    }
+
+    /// Match mounts at or below `prefix`.
+    pub fn path_prefix<P: AsRef<Path>>(mut self, prefix: P) -> Self {
+        self.path_prefixes.push(prefix.as_ref().to_path_buf());
+        self
+    }
+
+    /// Match mounts whose source starts with `source`, e.g. `/dev/sd`.
+    pub fn source(mut self, source: &str) -> Self {
+        self.sources.push(source.to_string());
+        self
+    }
+
+    pub fn matches(&self, mount: &MountInfo) -> bool {
+        let fstype = if self.fstypes.is_empty() {
+            !self.excluded_fstypes.contains(&mount.fstype)
+        } else {
+            self.fstypes.contains(&mount.fstype)
+        };
+        fstype
+            && (self.path_prefixes.is_empty()
+                || self
+                    .path_prefixes
+                    .iter()
+                    .any(|prefix| mount.mount_point.starts_with(prefix)))
+            && (self.sources.is_empty()
+                || self
+                    .sources
+                    .iter()
+                    .any(|source| mount.source.starts_with(source.as_str())))
+    }
+}
+
+impl Fanotify {
+    /// Adds a mount mark with `mode` to every mount at or below `root` that matches `filter`:
+    /// the mount containing `root`, and the mounts whose mount point is below it. Each mount is
+    /// marked once, and a mount hidden by another one mounted on top of it is skipped.
+    ///
+    /// The marks are added as a `MarkSet`, so either all of them are added or none. Returns the
+    /// mounts that were marked.
+    pub fn add_mount_tree<P: ?Sized + FanotifyPath>(
+        &self,
+        mode: u64,
+        root: &P,
+        filter: &MountFilter,
+    ) -> Result<Vec<MountInfo>, MountTreeError> {
+        let root = canonicalize(root.as_os_str())?;
+        let containing = MarkTarget::resolve(AT_FDCWD, Some(root.as_os_str()), true)?.mnt_id;
+        let mut tree: Vec<(PathBuf, MountInfo)> = Vec::new();
+        for mount in read()? {
+            let path = if mount.mount_id == containing {
+                root.clone()
+            } else if mount.mount_point.starts_with(&root) && mount.mount_point != root {
+                mount.mount_point.clone()
+            } else {
+                continue;
+            };
+            if !filter.matches(&mount) {
+                continue;
+            }
+            // Mounts are listed in the order they were mounted, so the last mount on a path is
+            // the one the path leads to.
+            tree.retain(|(marked, _)| *marked != path);
+            tree.push((path, mount));
+        }
+        tree.iter()
+            .fold(MarkSet::new(), |set, (path, _)| {
+                set.add_mountpoint(mode, path)
+            })
+            .apply(self)?;
+        Ok(tree.into_iter().map(|(_, mount)| mount).collect())
+    }
+}
+
+/// The error returned by `Fanotify::add_mount_tree`.
+#[derive(Debug)]
+pub enum MountTreeError {
+    /// The root or the mount table couldn't be read. No mark was added.
+    Mountinfo(Error),
+    /// Marking the mounts failed, and the marks already added were rolled back.
+    Mark(MarkSetError),
+}
+
+impl fmt::Display for MountTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MountTreeError::Mountinfo(err) => write!(f, "reading the mount table failed: {}", err),
+            MountTreeError::Mark(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for MountTreeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MountTreeError::Mountinfo(err) => Some(err),
+            MountTreeError::Mark(err) => Some(err),
+        }
+    }
+}
+
+impl From<Error> for MountTreeError {
+    fn from(err: Error) -> MountTreeError {
+        MountTreeError::Mountinfo(err)
+    }
+}
+
+impl From<MarkSetError> for MountTreeError {
+    fn from(err: MarkSetError) -> MountTreeError {
+        MountTreeError::Mark(err)
+    }
+}
+
+impl From<MountTreeError> for Error {
+    fn from(err: MountTreeError) -> Error {
+        match err {
+            MountTreeError::Mountinfo(err) => err,
+            MountTreeError::Mark(err) => err.into(),
+        }
+    }
+}
+
+/// A change of the mount table seen by a `MountWatcher`.
+#[derive(Debug)]
+pub enum MountChange {
+    /// A mount appeared. `marked` is true if it matched the filter and is now covered by a mark.
+    Mounted { mount: MountInfo, marked: bool },
+    /// A mount matching the filter appeared, but it couldn't be marked.
+    MarkFailed {
+        mount: MountInfo,
+        error: FanotifyError,
+    },
+    /// A mount went away. The kernel drops the marks of a mount (or of a filesystem, with its
+    /// last mount) by itself, `was_marked` tells whether the watcher had marked it.
+    Unmounted { mount: MountInfo, was_marked: bool },
+}
+
+/// A mount marked by a `MountWatcher`, with the object its mark was attached to.
+#[derive(Debug, Clone)]
+struct WatchedMount {
+    mount_id: u64,
+    target: MarkTarget,
+}
+
+/// Marks the mounts that appear after it is created, e.g. a USB disk or the root filesystem of
+/// a new container.
+///
+/// The watcher polls `/proc/self/mountinfo`, which the kernel flags with `POLLPRI` whenever the
+/// mount table of the process changes, and compares it with the table it saw last. New mounts
+/// matching the filter get a mount or filesystem mark with the watcher's mask; the marks of
+/// mounts that went away are removed from the group's mark registry. The mounts present when
+/// the watcher is created are left alone.
+///
+/// Only the mount namespace of the calling process is watched.
+pub struct MountWatcher {
+    fanotify: Fanotify,
+    mountinfo: File,
+    kind: MarkKind,
+    mask: u64,
+    filter: MountFilter,
+    mounts: Vec<MountInfo>,
+    watched: Vec<WatchedMount>,
+}
+
+impl MountWatcher {
+    /// Creates a watcher adding marks of `kind` with `mask` to `fanotify`. `kind` must be
+    /// `MarkKind::Mount` or `MarkKind::Filesystem`.
+    ///
+    /// The watcher holds a clone of `fanotify`, which shares its mark registry.
+    pub fn new(
+        fanotify: &Fanotify,
+        kind: MarkKind,
+        mask: u64,
+        filter: MountFilter,
+    ) -> Result<Self, Error> {
+        if !matches!(kind, MarkKind::Mount | MarkKind::Filesystem) {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "a mount watcher adds mount or filesystem marks",
+            ));
+        }
+        let mut watcher = Self {
+            fanotify: fanotify.clone(),
+            mountinfo: File::open("/proc/self/mountinfo")?,
+            kind,
+            mask,
+            filter,
+            mounts: Vec::new(),
+            watched: Vec::new(),
+        };
+        watcher.mounts = watcher.read_mounts()?;
+        Ok(watcher)
+    }
+
+    /// The mount table as of the last refresh.
+    pub fn mounts(&self) -> &[MountInfo] {
+        &self.mounts
+    }
+
+    /// Waits up to `timeout` milliseconds (or forever if negative) for the mount table to
+    /// change, then refreshes it. Returns no changes on timeout.
+    pub fn poll(&mut self, timeout: i32) -> Result<Vec<MountChange>, Error> {
+        let mut pollfd = libc::pollfd {
+            fd: self.mountinfo.as_raw_fd(),
+            events: libc::POLLPRI,
+            revents: 0,
+        };
+        loop {
+            match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+                -1 => {
+                    let err = Error::last_os_error();
+                    if err.kind() != std::io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+                0 => return Ok(Vec::new()),
+                _ => return self.refresh(),
+            }
+        }
+    }
+
+    /// Reads the mount table again and marks or forgets the mounts that changed since the last
+    /// refresh. Reading the table also clears the pending `POLLPRI`.
+    pub fn refresh(&mut self) -> Result<Vec<MountChange>, Error> {
+        let mounts = self.read_mounts()?;
+        let previous = std::mem::take(&mut self.mounts);
+        let mut changes = Vec::new();
+        for mount in &previous {
+            if mounts.iter().any(|m| m.mount_id == mount.mount_id) {
+                continue;
+            }
+            let was_marked = self.forget(mount, &mounts);
+            changes.push(MountChange::Unmounted {
+                mount: mount.clone(),
+                was_marked,
+            });
+        }
+        for mount in &mounts {
+            if previous.iter().any(|m| m.mount_id == mount.mount_id) {
+                continue;
+            }
+            if !self.filter.matches(mount) {
+                changes.push(MountChange::Mounted {
+                    mount: mount.clone(),
+                    marked: false,
+                });
+                continue;
+            }
+            changes.push(match self.mark(mount) {
+                Ok(()) => MountChange::Mounted {
+                    mount: mount.clone(),
+                    marked: true,
+                },
+                Err(error) => MountChange::MarkFailed {
+                    mount: mount.clone(),
+                    error,
+                },
+            });
+        }
+        self.mounts = mounts;
+        Ok(changes)
+    }
+
+    fn read_mounts(&mut self) -> Result<Vec<MountInfo>, Error> {
+        let mut contents = String::new();
+        self.mountinfo.seek(SeekFrom::Start(0))?;
+        self.mountinfo.read_to_string(&mut contents)?;
+        Ok(parse(&contents))
+    }
+
+    fn mark(&mut self, mount: &MountInfo) -> Result<(), FanotifyError> {
+        let target = MarkTarget::resolve(AT_FDCWD, Some(mount.mount_point.as_os_str()), true);
+        match self.kind {
+            MarkKind::Filesystem => self
+                .fanotify
+                .add_filesystem(self.mask, &mount.mount_point)?,
+            _ => self
+                .fanotify
+                .add_mountpoint(self.mask, &mount.mount_point)?,
+        }
+        if let Ok(target) = target {
+            self.watched.push(WatchedMount {
+                mount_id: mount.mount_id,
+                target,
+            });
+        }
+        Ok(())
+    }
+
+    /// Drops the bookkeeping of `mount`, which is no longer in `mounts`. Returns whether it was
+    /// marked by the watcher.
+    fn forget(&mut self, mount: &MountInfo, mounts: &[MountInfo]) -> bool {
+        let Some(index) = self
+            .watched
+            .iter()
+            .position(|watched| watched.mount_id == mount.mount_id)
+        else {
+            return false;
+        };
+        let watched = self.watched.remove(index);
+        // A filesystem mark lives as long as the superblock, i.e. until its last mount is gone.
+        if self.kind == MarkKind::Mount || !mounts.iter().any(|m| m.dev == mount.dev) {
+            self.fanotify.registry().forget(self.kind, &watched.target);
+        }
+        true
+    }
+}
+
+impl AsFd for MountWatcher {
+    /// The file descriptor of `/proc/self/mountinfo`, to wait for `POLLPRI` in an event loop
+    /// before calling `refresh`.
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.mountinfo.as_fd()
+    }
+}
+
+/// Where an open file is, as seen by a process, see `path_in_process`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessPath {
+    /// The path of the file in the mount namespace and root of the process. It was checked
+    /// to lead to the file through `/proc/<pid>/root`.
+    Resolved(PathBuf),
+    /// The file was deleted. The path is the one it had, translated like a resolved path
+    /// but unchecked, without the ` (deleted)` suffix the kernel appends.
+    Deleted(PathBuf),
+    /// The file is on a mount that isn't attached in the mount namespace of the process,
+    /// e.g. one unmounted with `MNT_DETACH` or cloned with `open_tree(2)`.
+    Detached,
+    /// No path leads to the file in the view of the process: it was renamed, is hidden by
+    /// another mount, or the process exited.
+    Unresolved,
+}
+
+/// Reads the mount table of process `pid`, whose mount points are relative to its root.
+pub fn read_process(pid: i32) -> Result<Vec<MountInfo>, Error> {
+    Ok(parse(&std::fs::read_to_string(format!(
+        "/proc/{}/mountinfo",
+        pid
+    ))?))
+}
+
+/// Translates the path of the open file `fd` into the view of process `pid`, which may have
+/// another mount namespace or root directory than the caller.
+///
+/// The path `/proc/self/fd/<fd>` links to is relative to the root of the caller, or to the
+/// root of the mount namespace the file was reached through when it is unreachable from it.
+/// It is rebased from the mount point of the file's mount in the caller's mount table to its
+/// mount point in the one of the process, or else from the root of the process. The result
+/// is checked by looking it up through `/proc/<pid>/root`.
+pub fn path_in_process<F: AsFd>(fd: &F, pid: i32) -> ProcessPath {
+    let fd = fd.as_fd().as_raw_fd();
+    let Ok(file) = MarkTarget::resolve(fd, None, false) else {
+        return ProcessPath::Unresolved;
+    };
+    let Ok(link) = read_link(format!("/proc/self/fd/{}", fd)) else {
+        return ProcessPath::Unresolved;
+    };
+    let deleted = is_unlinked(fd);
+    let link = strip_deleted(fd, link);
+    let Ok(process_mounts) = read_process(pid) else {
+        return ProcessPath::Unresolved;
+    };
+    let process_mount = process_mounts
+        .iter()
+        .find(|mount| mount.mount_id == file.mnt_id);
+
+    let mut candidates = Vec::new();
+    let own_mount = read()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|mount| mount.mount_id == file.mnt_id);
+    if let (Some(own), Some(process)) = (own_mount, process_mount) {
+        if let Ok(relative) = link.strip_prefix(&own.mount_point) {
+            candidates.push(process.mount_point.join(relative));
+        }
+    }
+    if let Ok(root) = read_link(format!("/proc/{}/root", pid)) {
+        if let Ok(relative) = link.strip_prefix(&root) {
+            candidates.push(Path::new("/").join(relative));
+        }
+    }
+    candidates.push(link);
+
+    if deleted {
+        return ProcessPath::Deleted(candidates.swap_remove(0));
+    }
+    let found = candidates.into_iter().find(|candidate| {
+        let mut in_root = OsString::from(format!("/proc/{}/root", pid));
+        in_root.push(candidate.as_os_str());
+        MarkTarget::resolve(AT_FDCWD, Some(&in_root), false)
+            .is_ok_and(|target| target.dev == file.dev && target.ino == file.ino)
+    });
+    match (found, process_mount) {
+        (Some(path), _) => ProcessPath::Resolved(path),
+        (None, None) => ProcessPath::Detached,
+        (None, Some(_)) => ProcessPath::Unresolved,
+    }
+}
@@ -0,0 +1,586 @@
+//! Attribution of events to the process that caused them, read from `/proc/<pid>`.
+//!
+//! A pid alone is a weak reference: the process may exit and its pid be reused by another one
+//! before `/proc` is read. When the group reports pidfds (`FanotifyBuilder::report_pidfd`) the
+//! information is checked against the pidfd, otherwise against the start time of the process.
+
+use crate::container::{self, ContainerId};
+use crate::exec::{dev_ino, elf_interpreter};
+use crate::high_level::{Event, FanEvent};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
+use std::fs::{metadata, read, read_link, read_to_string};
+use std::io::{Error, ErrorKind};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// What `/proc/<pid>` says about a process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub pid: i32,
+    pub ppid: i32,
+    /// The executable, `None` for kernel threads or if it can't be read.
+    pub exe: Option<PathBuf>,
+    /// The arguments of the process, empty for kernel threads and zombies.
+    pub cmdline: Vec<String>,
+    /// The command name, truncated by the kernel to 15 bytes.
+    pub comm: String,
+    pub uid: u32,
+    pub euid: u32,
+    pub gid: u32,
+    pub egid: u32,
+    /// The audit login uid, `None` if unset or not supported by the kernel.
+    pub loginuid: Option<u32>,
+    /// The audit session id, `None` if unset or not supported by the kernel.
+    pub sessionid: Option<u32>,
+    /// When the process started, in clock ticks since boot (see `sysconf(_SC_CLK_TCK)`).
+    /// Together with `pid` it identifies the process across pid reuse.
+    pub start_time: u64,
+    /// The cgroup v2 path of the process, `None` on systems using only cgroup v1.
+    pub cgroup: Option<String>,
+    /// The container of the process, found from its cgroups (see `container::from_cgroup`).
+    pub container: Option<ContainerId>,
+    /// Inode number of the mount namespace of the process, as in `/proc/<pid>/ns/mnt`.
+    pub mnt_ns: Option<u64>,
+    /// Inode number of the pid namespace of the process, as in `/proc/<pid>/ns/pid`.
+    pub pid_ns: Option<u64>,
+}
+
+/// The value of `loginuid` and `sessionid` when they are unset.
+const AUDIT_UNSET: u32 = u32::MAX;
+
+impl ProcessInfo {
+    /// Reads the information of process `pid`.
+    ///
+    /// Fails with `ErrorKind::NotFound` if the process doesn't exist, or if it exited and its
+    /// pid was reused while it was read, which is detected by a change of start time.
+    pub fn read(pid: i32) -> Result<Self, Error> {
+        let (comm, ppid, start_time) = read_stat(pid)?;
+        let status = read_to_string(format!("/proc/{}/status", pid)).map_err(not_found)?;
+        let (uids, gids) = (status_ids(&status, "Uid")?, status_ids(&status, "Gid")?);
+        let cmdline = read_cmdline(pid)?;
+        let cgroup = read_to_string(format!("/proc/{}/cgroup", pid)).unwrap_or_default();
+        let info = ProcessInfo {
+            pid,
+            ppid,
+            exe: read_link(format!("/proc/{}/exe", pid)).ok(),
+            cmdline,
+            comm,
+            uid: uids[0],
+            euid: uids[1],
+            gid: gids[0],
+            egid: gids[1],
+            loginuid: read_audit_id(pid, "loginuid"),
+            sessionid: read_audit_id(pid, "sessionid"),
+            start_time,
+            cgroup: container::unified_path(&cgroup).map(str::to_string),
+            container: container::from_cgroup(&cgroup),
+            mnt_ns: read_ns(pid, "mnt"),
+            pid_ns: read_ns(pid, "pid"),
+        };
+        if !info.is_running() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("process {} exited while it was read", pid),
+            ));
+        }
+        Ok(info)
+    }
+
+    /// Reads the information of the process `pidfd` refers to.
+    ///
+    /// The pid is taken from the pidfd, so it is the pid of the process in the pid namespace
+    /// of the caller. The information is only returned if the process is still alive once it
+    /// has been read, so it can't belong to another process that reused its pid.
+    pub fn from_pidfd<F: AsFd>(pidfd: &F) -> Result<Self, Error> {
+        let pidfd = pidfd.as_fd().as_raw_fd();
+        let fdinfo = read_to_string(format!("/proc/self/fdinfo/{}", pidfd))?;
+        let pid: i32 = fdinfo
+            .lines()
+            .find_map(|line| line.strip_prefix("Pid:"))
+            .and_then(|pid| pid.trim().parse().ok())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "not a pidfd"))?;
+        match pid {
+            -1 => return Err(Error::new(ErrorKind::NotFound, "process exited")),
+            0 => {
+                return Err(Error::new(
+                    ErrorKind::NotFound,
+                    "process is not in the pid namespace of the caller",
+                ))
+            }
+            _ => {}
+        }
+        let info = Self::read(pid)?;
+        check_alive(pidfd)?;
+        Ok(info)
+    }
+
+    /// Whether `pid` still refers to this process, i.e. it is running (or a zombie) with the
+    /// same start time.
+    pub fn is_running(&self) -> bool {
+        read_stat(self.pid).is_ok_and(|(_, _, start_time)| start_time == self.start_time)
+    }
+}
+
+/// The size and lifetime of the entries of a `ProcessCache`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ProcessCacheConfig {
+    /// How many processes the cache holds before evicting the least recently used one.
+    pub capacity: usize,
+    /// How long an entry is used before the process is read again, as its command line and
+    /// ids may change during its lifetime.
+    pub ttl: Duration,
+}
+
+impl Default for ProcessCacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            ttl: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Hit and miss counters of a `ProcessCache`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct ProcessCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// A process in the ancestry of another, see `ProcessCache::ancestors`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ancestor {
+    pub pid: i32,
+    /// The executable, `None` if it can't be read and no exec of the process was recorded.
+    pub exe: Option<PathBuf>,
+    pub start_time: u64,
+}
+
+/// How often `Fanotify::read_event` prunes the cache of its group, see `ProcessCache::prune`.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How deep `ProcessCache::ancestors` walks, in case start times fail to break a cycle.
+const MAX_ANCESTORS: usize = 256;
+
+/// A process of the tree recorded from exec events.
+#[derive(Debug)]
+struct ExecNode {
+    /// The parent at the first exec of the process, which outlives a reparenting to a reaper.
+    ppid: i32,
+    start_time: u64,
+    exe: Option<PathBuf>,
+    /// The device and inode of the ELF interpreter of `exe`, whose exec is part of the exec of
+    /// `exe`.
+    loader: Option<(u64, u64)>,
+    /// Order of recording, to evict the oldest node when the tree is full.
+    recorded: u64,
+}
+
+/// A step of a ppid chain.
+struct Lineage {
+    ppid: i32,
+    exe: Option<PathBuf>,
+    start_time: u64,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    info: ProcessInfo,
+    read_at: Instant,
+    /// Position in `ProcessCache::lru`.
+    used: u64,
+}
+
+/// A bounded cache of `ProcessInfo`, so that the many events of a process only cost one read
+/// of `/proc/<pid>/stat` each instead of a full `ProcessInfo::read`.
+///
+/// An entry is only used for the process it was read from: lookups compare the start time
+/// of the process currently holding the pid, so a reused pid replaces the entry. Entries are
+/// dropped when they are older than the TTL, when the process is found gone, and by `prune`.
+///
+/// Groups built with `FanotifyBuilder::process_info` look up the process of each event in
+/// their cache, see `Fanotify::process_cache`.
+///
+/// The cache also keeps a tree of the processes seen executing a file (`record_exec`), so
+/// that `ancestors` can follow the lineage of processes whose parents already exited.
+#[derive(Debug)]
+pub struct ProcessCache {
+    config: ProcessCacheConfig,
+    entries: HashMap<i32, CacheEntry>,
+    /// Pids by last use, least recent first.
+    lru: BTreeMap<u64, i32>,
+    next_use: u64,
+    stats: ProcessCacheStats,
+    /// Processes seen executing a file, by pid.
+    tree: HashMap<i32, ExecNode>,
+    next_exec: u64,
+    pruned_at: Instant,
+}
+
+impl ProcessCache {
+    pub fn new(config: ProcessCacheConfig) -> Self {
+        Self {
+            config,
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            next_use: 0,
+            stats: ProcessCacheStats::default(),
+            tree: HashMap::new(),
+            next_exec: 0,
+            pruned_at: Instant::now(),
+        }
+    }
+
+    pub fn config(&self) -> ProcessCacheConfig {
+        self.config
+    }
+
+    /// The information of process `pid`, from the cache if it holds a fresh entry for the
+    /// process currently running with this pid, or else read from `/proc`.
+    ///
+    /// With a `pidfd` of the process, it is checked to still be running after the lookup, so
+    /// that the returned information can't belong to a process that reused its pid.
+    pub fn get(&mut self, pid: i32, pidfd: Option<BorrowedFd<'_>>) -> Result<ProcessInfo, Error> {
+        let start_time = match read_stat(pid) {
+            Ok((_, _, start_time)) => start_time,
+            Err(err) => {
+                self.remove(pid);
+                return Err(err);
+            }
+        };
+        let fresh = self.entries.get(&pid).is_some_and(|entry| {
+            entry.info.start_time == start_time && entry.read_at.elapsed() < self.config.ttl
+        });
+        let info = if fresh {
+            self.stats.hits += 1;
+            self.touch(pid);
+            self.entries[&pid].info.clone()
+        } else {
+            self.stats.misses += 1;
+            let info = match pidfd {
+                Some(pidfd) => ProcessInfo::from_pidfd(&pidfd)?,
+                None => ProcessInfo::read(pid)?,
+            };
+            self.insert(info.clone());
+            info
+        };
+        if let Some(pidfd) = pidfd {
+            check_alive(pidfd.as_raw_fd())?;
+        }
+        Ok(info)
+    }
+
+    fn touch(&mut self, pid: i32) {
+        let entry = self.entries.get_mut(&pid).unwrap();
+        self.lru.remove(&entry.used);
+        self.next_use += 1;
+        entry.used = self.next_use;
+        self.lru.insert(self.next_use, pid);
+    }
+
+    fn insert(&mut self, info: ProcessInfo) {
+        let pid = info.pid;
+        self.remove(pid);
+        if self.config.capacity == 0 {
+            return;
+        }
+        while self.entries.len() >= self.config.capacity {
+            let Some((_, oldest)) = self.lru.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+        self.next_use += 1;
+        self.lru.insert(self.next_use, pid);
+        self.entries.insert(
+            pid,
+            CacheEntry {
+                info,
+                read_at: Instant::now(),
+                used: self.next_use,
+            },
+        );
+    }
+
+    /// Drops the entry of `pid`, if any.
+    pub fn remove(&mut self, pid: i32) -> Option<ProcessInfo> {
+        let entry = self.entries.remove(&pid)?;
+        self.lru.remove(&entry.used);
+        Some(entry.info)
+    }
+
+    /// Records that the process of `event` executes the file of the event, if it is a
+    /// `FAN_OPEN_EXEC` or `FAN_OPEN_EXEC_PERM` event whose process could be read. Returns
+    /// whether it was recorded.
+    ///
+    /// The process is added to the tree used by `ancestors`, with its parent at the time of
+    /// its first exec. Its cache entry is dropped, as the exec changes its executable and
+    /// command line once it completes. A later exec of the same process replaces the
+    /// executable of its node.
+    ///
+    /// Dynamically linked programs also open their ELF interpreter (e.g. `ld-linux.so`) for
+    /// execution during the same exec. It is recognized from the `PT_INTERP` header of the
+    /// program, see `ExecTracker`. This needs the file descriptor of the event, so groups
+    /// reporting file ids keep the first file a process executed.
+    pub fn record_exec(&mut self, event: &Event) -> bool {
+        if !event
+            .events
+            .iter()
+            .any(|event| matches!(event, FanEvent::OpenExec | FanEvent::OpenExecPerm))
+        {
+            return false;
+        }
+        let (ppid, start_time) = match &event.process {
+            Some(info) if info.pid == event.pid => (info.ppid, info.start_time),
+            _ => match read_stat(event.pid) {
+                Ok((_, ppid, start_time)) => (ppid, start_time),
+                Err(_) => return false,
+            },
+        };
+        self.remove(event.pid);
+        let exe = Some(Path::new(&event.path))
+            .filter(|path| path.is_absolute())
+            .map(Path::to_path_buf);
+        if let Some(node) = self.tree.get_mut(&event.pid) {
+            if node.start_time == start_time {
+                let file = dev_ino(event.fd);
+                if file.is_some() && node.loader.take() != file {
+                    self.next_exec += 1;
+                    node.exe = exe;
+                    node.loader = elf_interpreter(event);
+                    node.recorded = self.next_exec;
+                }
+                return true;
+            }
+        }
+        if self.tree.len() >= self.config.capacity {
+            let oldest = self.tree.iter().min_by_key(|(_, node)| node.recorded);
+            if let Some(oldest) = oldest.map(|(pid, _)| *pid) {
+                self.tree.remove(&oldest);
+            }
+        }
+        if self.config.capacity == 0 {
+            return false;
+        }
+        self.next_exec += 1;
+        self.tree.insert(
+            event.pid,
+            ExecNode {
+                ppid,
+                start_time,
+                exe,
+                loader: elf_interpreter(event),
+                recorded: self.next_exec,
+            },
+        );
+        true
+    }
+
+    /// The ancestors of process `pid`, from its parent up to the first process (`init` or the
+    /// init of its pid namespace). Empty if the process can't be found.
+    ///
+    /// Each step prefers the parent recorded by `record_exec` over the current parent in
+    /// `/proc`, which is a reaper once the original parent exited. A parent is only accepted
+    /// if it started before its child, so a reused pid ends the walk instead of leading to an
+    /// unrelated process.
+    pub fn ancestors(&mut self, pid: i32) -> Vec<Ancestor> {
+        let mut ancestors = Vec::new();
+        let Some(mut current) = self.lineage(pid, None) else {
+            return ancestors;
+        };
+        while current.ppid > 0 && ancestors.len() < MAX_ANCESTORS {
+            let pid = current.ppid;
+            let Some(parent) = self.lineage(pid, Some(current.start_time)) else {
+                break;
+            };
+            ancestors.push(Ancestor {
+                pid,
+                exe: parent.exe.clone(),
+                start_time: parent.start_time,
+            });
+            current = parent;
+        }
+        ancestors
+    }
+
+    /// The parent, executable and start time of process `pid`, provided it started no later
+    /// than `started_by`.
+    fn lineage(&mut self, pid: i32, started_by: Option<u64>) -> Option<Lineage> {
+        let in_time = |start_time: u64| started_by.is_none_or(|limit| start_time <= limit);
+        let info = self
+            .get(pid, None)
+            .ok()
+            .filter(|info| in_time(info.start_time));
+        let node = self.tree.get(&pid).filter(|node| in_time(node.start_time));
+        match (info, node) {
+            (Some(info), node) => {
+                let node = node.filter(|node| node.start_time == info.start_time);
+                Some(Lineage {
+                    ppid: node.map_or(info.ppid, |node| node.ppid),
+                    exe: info.exe.or_else(|| node?.exe.clone()),
+                    start_time: info.start_time,
+                })
+            }
+            // The process exited, or its pid now belongs to a younger process.
+            (None, Some(node)) => Some(Lineage {
+                ppid: node.ppid,
+                exe: node.exe.clone(),
+                start_time: node.start_time,
+            }),
+            (None, None) => None,
+        }
+    }
+
+    /// Drops the entries of processes that exited and the entries older than the TTL. Exits
+    /// are detected by checking `/proc/<pid>` still belongs to the process. Groups prune their
+    /// cache from `Fanotify::read_event`, at most once a second.
+    ///
+    /// Processes of the exec tree are dropped once they exited and no other process of the
+    /// tree descends from them.
+    pub fn prune(&mut self) {
+        self.pruned_at = Instant::now();
+        let ttl = self.config.ttl;
+        let stale: Vec<i32> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.read_at.elapsed() >= ttl || !entry.info.is_running())
+            .map(|(pid, _)| *pid)
+            .collect();
+        for pid in stale {
+            self.remove(pid);
+        }
+
+        let exited: Vec<i32> = self
+            .tree
+            .iter()
+            .filter(|(pid, node)| {
+                read_stat(**pid).map_or(true, |(_, _, start_time)| start_time != node.start_time)
+            })
+            .map(|(pid, _)| *pid)
+            .collect();
+        for pid in exited {
+            if !self.tree.values().any(|node| node.ppid == pid) {
+                self.tree.remove(&pid);
+            }
+        }
+    }
+
+    /// Prunes the cache if it wasn't pruned for a while.
+    pub(crate) fn prune_if_due(&mut self) {
+        if self.pruned_at.elapsed() >= PRUNE_INTERVAL {
+            self.prune();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.lru.clear();
+        self.tree.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn stats(&self) -> ProcessCacheStats {
+        self.stats
+    }
+}
+
+/// Checks that the process of `pidfd` hasn't exited, by sending it the null signal.
+fn check_alive(pidfd: i32) -> Result<(), Error> {
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_pidfd_send_signal,
+            pidfd,
+            0,
+            std::ptr::null::<libc::siginfo_t>(),
+            0,
+        )
+    };
+    if ret != 0 {
+        return Err(not_found(Error::last_os_error()));
+    }
+    Ok(())
+}
+
+/// The arguments in `/proc/<pid>/cmdline`.
+pub(crate) fn read_cmdline(pid: i32) -> Result<Vec<String>, Error> {
+    Ok(read(format!("/proc/{}/cmdline", pid))
+        .map_err(not_found)?
+        .split(|byte| *byte == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| OsStr::from_bytes(arg).to_string_lossy().into_owned())
+        .collect())
+}
+
+/// The command name, parent pid and start time in `/proc/<pid>/stat`.
+fn read_stat(pid: i32) -> Result<(String, i32, u64), Error> {
+    let stat = read_to_string(format!("/proc/{}/stat", pid)).map_err(not_found)?;
+    parse_stat(&stat).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("can't parse /proc/{}/stat", pid),
+        )
+    })
+}
+
+fn parse_stat(stat: &str) -> Option<(String, i32, u64)> {
+    // The command name is in parentheses and may contain anything, including ") ".
+    let (head, fields) = stat.rsplit_once(") ")?;
+    let (_, comm) = head.split_once(" (")?;
+    let fields: Vec<&str> = fields.split_whitespace().collect();
+    // `fields` starts at the third field (state): ppid is the 4th, starttime the 22nd.
+    Some((
+        comm.to_string(),
+        fields.get(1)?.parse().ok()?,
+        fields.get(19)?.parse().ok()?,
+    ))
+}
+
+/// The real and effective ids of the `Uid` or `Gid` line of `/proc/<pid>/status`.
+fn status_ids(status: &str, name: &str) -> Result<[u32; 2], Error> {
+    let ids: Vec<u32> = status
+        .lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter_map(|id| id.parse().ok())
+        .collect();
+    match ids[..] {
+        [real, effective, ..] => Ok([real, effective]),
+        _ => Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("no {} line in status", name),
+        )),
+    }
+}
+
+fn read_ns(pid: i32, name: &str) -> Option<u64> {
+    Some(metadata(format!("/proc/{}/ns/{}", pid, name)).ok()?.ino())
+}
+
+fn read_audit_id(pid: i32, name: &str) -> Option<u32> {
+    read_to_string(format!("/proc/{}/{}", pid, name))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .filter(|id| *id != AUDIT_UNSET)
+}
+
+/// `/proc/<pid>` of an exited process vanishes (`ENOENT`) or has its files return `ESRCH`.
+fn not_found(err: Error) -> Error {
+    match err.raw_os_error() {
+        Some(libc::ESRCH) => Error::new(ErrorKind::NotFound, err),
+        _ => err,
+    }
+}
@@ -0,0 +1,319 @@
+use crate::high_level::{is_dir_at, IgnoreOptions};
+use crate::low_level::{
+    AT_FDCWD, FAN_EVENT_ON_CHILD, FAN_MARK_ADD, FAN_MARK_DONT_FOLLOW, FAN_MARK_EVICTABLE,
+    FAN_MARK_FILESYSTEM, FAN_MARK_FLUSH, FAN_MARK_IGNORE, FAN_MARK_IGNORED_MASK,
+    FAN_MARK_IGNORED_SURV_MODIFY, FAN_MARK_MNTNS, FAN_MARK_MOUNT, FAN_MARK_REMOVE, FAN_ONDIR,
+};
+use std::ffi::{CString, OsStr};
+use std::fmt;
+use std::fs::read_link;
+use std::io::Error;
+use std::mem::MaybeUninit;
+use std::os::fd::{AsRawFd, BorrowedFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// What a mark is attached to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum MarkKind {
+    /// A file or directory (`FAN_MARK_INODE`).
+    Inode,
+    /// The mount containing the path (`FAN_MARK_MOUNT`).
+    Mount,
+    /// The filesystem containing the path (`FAN_MARK_FILESYSTEM`).
+    Filesystem,
+    /// A mount namespace (`FAN_MARK_MNTNS`), given by a file descriptor of `/proc/<pid>/ns/mnt`.
+    MountNamespace,
+}
+
+impl MarkKind {
+    fn from_mark_flags(flags: u32) -> Self {
+        // FAN_MARK_MNTNS shares its bits with FAN_MARK_MOUNT and FAN_MARK_FILESYSTEM.
+        if flags & FAN_MARK_MNTNS == FAN_MARK_MNTNS {
+            MarkKind::MountNamespace
+        } else if flags & FAN_MARK_FILESYSTEM != 0 {
+            MarkKind::Filesystem
+        } else if flags & FAN_MARK_MOUNT != 0 {
+            MarkKind::Mount
+        } else {
+            MarkKind::Inode
+        }
+    }
+
+    /// The `fanotify_mark` flag selecting this kind of mark.
+    pub fn to_mark_flags(self) -> u32 {
+        match self {
+            MarkKind::Inode => 0,
+            MarkKind::Mount => FAN_MARK_MOUNT,
+            MarkKind::Filesystem => FAN_MARK_FILESYSTEM,
+            MarkKind::MountNamespace => FAN_MARK_MNTNS,
+        }
+    }
+}
+
+/// Identifies a mark in the registry of a `Fanotify` group.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MarkId(u64);
+
+impl fmt::Display for MarkId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The object a mark was resolved to when it was added.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MarkTarget {
+    /// Device of the filesystem containing the object.
+    pub dev: u64,
+    /// Inode number of the object.
+    pub ino: u64,
+    /// Id of the mount the object was reached through, as in `/proc/self/mountinfo`.
+    /// Zero if the kernel doesn't report it (before Linux 5.8).
+    pub mnt_id: u64,
+}
+
+impl MarkTarget {
+    /// Resolves `path` relative to `dirfd`, or `dirfd` itself if `path` is `None`.
+    pub(crate) fn resolve(dirfd: i32, path: Option<&OsStr>, follow: bool) -> Result<Self, Error> {
+        let path = CString::new(path.map_or(&[][..], OsStr::as_bytes))?;
+        let mut flags = if follow { 0 } else { libc::AT_SYMLINK_NOFOLLOW };
+        if path.as_bytes().is_empty() {
+            flags |= libc::AT_EMPTY_PATH;
+        }
+        let mut stx = MaybeUninit::<libc::statx>::zeroed();
+        let ret = unsafe {
+            libc::statx(
+                dirfd,
+                path.as_ptr(),
+                flags,
+                libc::STATX_INO | libc::STATX_MNT_ID,
+                stx.as_mut_ptr(),
+            )
+        };
+        if ret != 0 {
+            return Err(Error::last_os_error());
+        }
+        let stx = unsafe { stx.assume_init() };
+        Ok(MarkTarget {
+            dev: libc::makedev(stx.stx_dev_major, stx.stx_dev_minor),
+            ino: stx.stx_ino,
+            mnt_id: if stx.stx_mask & libc::STATX_MNT_ID != 0 {
+                stx.stx_mnt_id
+            } else {
+                0
+            },
+        })
+    }
+
+    /// Whether a mark of `kind` on `self` and one on `other` are the same kernel mark.
+    pub fn same_mark(&self, kind: MarkKind, other: &MarkTarget) -> bool {
+        match kind {
+            MarkKind::Inode | MarkKind::MountNamespace => {
+                self.dev == other.dev && self.ino == other.ino
+            }
+            MarkKind::Mount if self.mnt_id != 0 && other.mnt_id != 0 => self.mnt_id == other.mnt_id,
+            MarkKind::Mount => self.dev == other.dev && self.ino == other.ino,
+            MarkKind::Filesystem => self.dev == other.dev,
+        }
+    }
+}
+
+/// A mark added through a `Fanotify` group, as recorded by its registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mark {
+    pub id: MarkId,
+    pub kind: MarkKind,
+    /// Events the mark listens for.
+    pub mask: u64,
+    /// Events the mark ignores.
+    pub ignored_mask: u64,
+    /// How the ignore mask was added, used when the mark is replayed.
+    pub ignore_options: IgnoreOptions,
+    /// Absolute path the mark was added with, if it could be determined.
+    pub path: Option<PathBuf>,
+    pub target: MarkTarget,
+    /// The namespace of a `MarkKind::MountNamespace` mark, which has no path.
+    ns: Option<NamespaceFd>,
+}
+
+impl Mark {
+    fn is_empty(&self) -> bool {
+        (self.mask | self.ignored_mask) & !(FAN_ONDIR | FAN_EVENT_ON_CHILD) == 0
+    }
+
+    /// The `dirfd` and `path` to give `fanotify_mark` to change the mark again, `None` if the
+    /// object of the mark can't be reached.
+    pub(crate) fn location(&self) -> Option<(i32, Option<&OsStr>)> {
+        match (&self.ns, &self.path) {
+            (Some(ns), _) => Some((ns.0.as_raw_fd(), None)),
+            (None, Some(path)) => Some((AT_FDCWD, Some(path.as_os_str()))),
+            (None, None) => None,
+        }
+    }
+}
+
+/// A duplicate of the file descriptor a mount namespace was marked through, shared by the
+/// copies of the mark. Marks are compared through their `target`, so it is left out.
+#[derive(Debug, Clone)]
+struct NamespaceFd(Arc<OwnedFd>);
+
+impl PartialEq for NamespaceFd {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for NamespaceFd {}
+
+/// A copy of the marks of a group, which can be replayed onto another group with
+/// `Fanotify::restore`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MarkSnapshot {
+    marks: Vec<Mark>,
+}
+
+impl MarkSnapshot {
+    pub fn marks(&self) -> &[Mark] {
+        &self.marks
+    }
+}
+
+/// Bookkeeping of the marks added to a group, shared by its clones.
+#[derive(Debug, Default)]
+pub(crate) struct MarkRegistry {
+    next_id: u64,
+    marks: Vec<Mark>,
+}
+
+impl MarkRegistry {
+    pub(crate) fn marks(&self) -> Vec<Mark> {
+        self.marks.clone()
+    }
+
+    pub(crate) fn get(&self, id: MarkId) -> Option<&Mark> {
+        self.marks.iter().find(|mark| mark.id == id)
+    }
+
+    /// The mark of `kind` on the object `target`, if any.
+    pub(crate) fn find(&self, kind: MarkKind, target: &MarkTarget) -> Option<&Mark> {
+        self.marks
+            .iter()
+            .find(|mark| mark.kind == kind && mark.target.same_mark(kind, target))
+    }
+
+    /// Drops the mark of `kind` on `target` without touching the kernel, for marks the kernel
+    /// removed by itself.
+    pub(crate) fn forget(&mut self, kind: MarkKind, target: &MarkTarget) {
+        self.marks
+            .retain(|mark| mark.kind != kind || !mark.target.same_mark(kind, target));
+    }
+
+    pub(crate) fn snapshot(&self) -> MarkSnapshot {
+        MarkSnapshot {
+            marks: self.marks.clone(),
+        }
+    }
+
+    /// Records a successful `fanotify_mark` call. `dirfd` and `path` are resolved again to
+    /// find the object that was marked; the call is not recorded if that fails.
+    ///
+    /// Evictable marks are not recorded, as the kernel may drop them at any time.
+    pub(crate) fn record(&mut self, flags: u32, mask: u64, dirfd: i32, path: Option<&OsStr>) {
+        if flags & FAN_MARK_EVICTABLE != 0 {
+            return;
+        }
+        let kind = MarkKind::from_mark_flags(flags);
+        if flags & FAN_MARK_FLUSH != 0 {
+            self.marks.retain(|mark| mark.kind != kind);
+            return;
+        }
+        let follow = flags & FAN_MARK_DONT_FOLLOW == 0;
+        let target = match MarkTarget::resolve(dirfd, path, follow) {
+            Ok(target) => target,
+            Err(_) => return,
+        };
+        // The kernel drops FAN_EVENT_ON_CHILD from the marks of mounts, filesystems and
+        // non-directories.
+        let mask = if kind != MarkKind::Inode || !is_dir_at(dirfd, path, follow) {
+            mask & !FAN_EVENT_ON_CHILD
+        } else {
+            mask
+        };
+        let ignore = flags & (FAN_MARK_IGNORED_MASK | FAN_MARK_IGNORE) != 0;
+        let index = self
+            .marks
+            .iter()
+            .position(|mark| mark.kind == kind && mark.target.same_mark(kind, &target));
+        if flags & FAN_MARK_ADD != 0 {
+            let index = index.unwrap_or_else(|| {
+                self.next_id += 1;
+                self.marks.push(Mark {
+                    id: MarkId(self.next_id),
+                    kind,
+                    mask: 0,
+                    ignored_mask: 0,
+                    ignore_options: IgnoreOptions::default(),
+                    path: None,
+                    target,
+                    ns: None,
+                });
+                self.marks.len() - 1
+            });
+            let mark = &mut self.marks[index];
+            if ignore {
+                mark.ignored_mask |= mask;
+                mark.ignore_options = IgnoreOptions {
+                    survive_modify: flags & FAN_MARK_IGNORED_SURV_MODIFY != 0,
+                    ondir: flags & FAN_MARK_IGNORE == FAN_MARK_IGNORE,
+                    evictable: false,
+                };
+            } else {
+                mark.mask |= mask;
+            }
+            if let Some(path) = absolute_path(dirfd, path) {
+                mark.path = Some(path);
+            }
+            if kind == MarkKind::MountNamespace && mark.ns.is_none() {
+                let ns = unsafe { BorrowedFd::borrow_raw(dirfd) }.try_clone_to_owned();
+                mark.ns = ns.ok().map(|ns| NamespaceFd(Arc::new(ns)));
+            }
+        } else if let (true, Some(index)) = (flags & FAN_MARK_REMOVE != 0, index) {
+            let mark = &mut self.marks[index];
+            if ignore {
+                mark.ignored_mask &= !mask;
+            } else {
+                mark.mask &= !mask;
+            }
+            if mark.is_empty() {
+                self.marks.remove(index);
+            }
+        }
+    }
+}
+
+/// The absolute path of `path` relative to `dirfd`, or of `dirfd` itself if `path` is `None`.
+fn absolute_path(dirfd: i32, path: Option<&OsStr>) -> Option<PathBuf> {
+    let path = path.map(Path::new);
+    if let Some(path) = path.filter(|path| path.is_absolute()) {
+        return Some(path.to_path_buf());
+    }
+    let base = if dirfd == AT_FDCWD {
+        std::env::current_dir().ok()?
+    } else {
+        // Descriptors of objects outside the filesystem tree, like namespaces, link to
+        // pseudo-paths such as `mnt:[4026531840]`.
+        Some(read_link(format!("/proc/self/fd/{}", dirfd)).ok()?)
+            .filter(|base| base.is_absolute())?
+    };
+    Some(match path {
+        Some(path) => base.join(path),
+        None => base,
+    })
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "config")]
+
+#[test]
+fn config_test() {
+    use fanotify::config::WatchConfig;
+    use fanotify::high_level::{
+        FanEvent, FanotifyMode, IgnoreOptions, FAN_CLOSE_WRITE, FAN_MODIFY, FAN_ONDIR, FAN_OPEN,
+    };
+    use fanotify::low_level::{FAN_NONBLOCK, FAN_REPORT_FID};
+    let dir = "/tmp/fanotify_config_test";
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(format!("{}/sub", dir)).unwrap();
+    let config = WatchConfig::from_toml(
+        r#"
+        [group]
+        class = "notif"
+        nonblocking = true
+
+        [[mark]]
+        kind = "inode"
+        path = "/tmp/fanotify_config_test"
+        events = ["open", "close_write"]
+
+        [[mark]]
+        kind = "inode"
+        path = "/tmp/fanotify_config_test/sub"
+        ignore = ["modify"]
+        ignore_options = { survive_modify = true, ondir = true }
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.group.class, FanotifyMode::NOTIF);
+    assert_eq!(
+        config.marks[0].events,
+        [FanEvent::Open, FanEvent::CloseWrite]
+    );
+    assert_eq!(
+        WatchConfig::from_toml(&config.to_toml().unwrap()).unwrap(),
+        config
+    );
+    assert!(WatchConfig::from_toml(
+        "[[mark]]\nkind = \"inode\"\npath = \"/\"\nevents = [\"nope\"]"
+    )
+    .is_err());
+    assert!(config.group.to_builder().has_flag(FAN_NONBLOCK));
+    assert!(!config.group.to_builder().has_flag(FAN_REPORT_FID));
+
+    let ft = config.group.to_builder().register().unwrap();
+    let changes = ft.apply_config(&config).unwrap();
+    assert_eq!(changes.ops().len(), 2);
+    let marks = ft.marks();
+    assert_eq!(marks.len(), 2);
+    assert_eq!(marks[0].mask, FAN_OPEN | FAN_CLOSE_WRITE);
+    assert_eq!(marks[1].ignored_mask, FAN_MODIFY | FAN_ONDIR);
+    assert!(ft.apply_config(&config).unwrap().is_empty());
+
+    let mut reloaded = config.clone();
+    reloaded.marks[0].events = vec![FanEvent::Open, FanEvent::Modify];
+    reloaded.marks.remove(1);
+    let changes = ft.apply_config(&reloaded).unwrap();
+    assert_eq!(changes.ops().len(), 3);
+    let marks = ft.marks();
+    assert_eq!(marks.len(), 1);
+    assert_eq!(marks[0].mask, FAN_OPEN | FAN_MODIFY);
+    assert!(ft.drift().unwrap().is_empty());
+
+    let mut broken = reloaded.clone();
+    broken.marks[0].events = vec![FanEvent::CloseWrite];
+    broken.marks[0].path = "/tmp/fanotify_config_test/missing".into();
+    assert!(ft.apply_config(&broken).is_err());
+    let restored = ft.marks();
+    assert_eq!(restored.len(), 1);
+    assert_eq!(restored[0].mask, marks[0].mask);
+    assert_eq!(restored[0].target, marks[0].target);
+
+    // New ignore options replace the mark, events included.
+    let mut both = config.clone();
+    both.marks[1].events = vec![FanEvent::Open];
+    let ft = config.group.to_builder().register().unwrap();
+    ft.apply_config(&both).unwrap();
+    both.marks[1].ignore_options = IgnoreOptions::default();
+    let changes = ft.apply_config(&both).unwrap();
+    assert_eq!(changes.ops().len(), 4);
+    let marks = ft.marks();
+    assert_eq!(marks[1].mask, FAN_OPEN);
+    assert_eq!(marks[1].ignored_mask, FAN_MODIFY);
+    assert_eq!(marks[1].ignore_options, IgnoreOptions::default());
+    assert!(ft.drift().unwrap().is_empty());
+    both.marks[1].ignore_options.ondir = true;
+    assert_eq!(ft.apply_config(&both).unwrap().ops().len(), 4);
+    assert_eq!(ft.marks()[1].ignored_mask, FAN_MODIFY | FAN_ONDIR);
+    assert!(ft.drift().unwrap().is_empty());
+    std::fs::remove_dir_all(dir).unwrap();
+}
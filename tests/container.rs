@@ -0,0 +1,76 @@
+#[test]
+fn container_id_test() {
+    use fanotify::container::{container_id, ContainerRuntime};
+    let id = "9c5a5e2d3b1f4e6a8c7d9e0f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b";
+    for (path, runtime) in [
+        (
+            format!(
+                "/kubepods.slice/kubepods-pod1.slice/cri-containerd-{}.scope",
+                id
+            ),
+            ContainerRuntime::Containerd,
+        ),
+        (
+            format!(
+                "/kubepods/burstable/pod0b6e1c1e-1c1e-4b4b-9b9b-0b6e1c1e1c1e/{}",
+                id
+            ),
+            ContainerRuntime::Containerd,
+        ),
+        (
+            format!("/kubepods.slice/kubepods-pod1.slice/crio-{}.scope", id),
+            ContainerRuntime::CriO,
+        ),
+        (
+            format!("/kubepods/pod1/crio-{}", id),
+            ContainerRuntime::CriO,
+        ),
+        (
+            format!("/system.slice/docker-{}.scope", id),
+            ContainerRuntime::Docker,
+        ),
+        (format!("/docker/{}", id), ContainerRuntime::Docker),
+        (
+            format!("/machine.slice/libpod-{}.scope/container", id),
+            ContainerRuntime::Podman,
+        ),
+        (
+            format!("/libpod_parent/libpod-{}", id),
+            ContainerRuntime::Podman,
+        ),
+        (format!("/custom/{}", id), ContainerRuntime::Unknown),
+    ] {
+        let container = container_id(&path).unwrap();
+        assert_eq!(container.runtime, runtime, "{}", path);
+        assert_eq!(container.id, id);
+    }
+    for path in [
+        "/".to_string(),
+        "/system.slice/sshd.service".to_string(),
+        format!("/machine.slice/libpod-conmon-{}.scope", id),
+        format!("/system.slice/crio-conmon-{}.scope", id),
+        format!("/docker/{}", &id[..12]),
+    ] {
+        assert!(container_id(&path).is_none(), "{}", path);
+    }
+}
+
+#[test]
+fn from_cgroup_test() {
+    use fanotify::container::{from_cgroup, unified_path, ContainerRuntime};
+    let id = "9c5a5e2d3b1f4e6a8c7d9e0f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b";
+    let v2 = format!("0::/system.slice/docker-{}.scope\n", id);
+    assert_eq!(
+        unified_path(&v2),
+        Some(format!("/system.slice/docker-{}.scope", id).as_str())
+    );
+    assert_eq!(from_cgroup(&v2).unwrap().runtime, ContainerRuntime::Docker);
+    // Hybrid hierarchy with the container only visible in the v1 controllers.
+    let hybrid = format!(
+        "12:memory:/docker/{}\n1:name=systemd:/docker/{}\n0::/\n",
+        id, id
+    );
+    assert_eq!(unified_path(&hybrid), Some("/"));
+    assert_eq!(from_cgroup(&hybrid).unwrap().id, id);
+    assert!(from_cgroup("0::/user.slice\n").is_none());
+}
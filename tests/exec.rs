@@ -0,0 +1,112 @@
+#[test]
+fn exec_tracker_test() {
+    use fanotify::exec::{ExecKind, ExecTracker};
+    use fanotify::high_level::{FanotifyBuilder, FAN_EVENT_ON_CHILD, FAN_OPEN, FAN_OPEN_EXEC};
+    use std::io::Write;
+    use std::os::unix::fs::MetadataExt;
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+    let dir = std::env::temp_dir().join("fanotify_exec_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let sh = dir.join("sh");
+    std::fs::copy("/bin/sh", &sh).unwrap();
+    let script = dir.join("script");
+    std::fs::write(&script, "read line\n").unwrap();
+    let ft = FanotifyBuilder::new().nonblocking().register().unwrap();
+    ft.add_path(FAN_OPEN_EXEC | FAN_OPEN | FAN_EVENT_ON_CHILD, &dir)
+        .unwrap();
+    let mut tracker = ExecTracker::new().with_hasher(|fd| {
+        let meta = std::fs::File::from(fd.try_clone_to_owned()?).metadata()?;
+        Ok(meta.len().to_ne_bytes().to_vec())
+    });
+
+    // The script blocks until its stdin is closed, so that its command line can be read.
+    let mut child = Command::new(&sh)
+        .arg("script")
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let pid = child.id() as i32;
+    let mut records = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while records.len() < 2 {
+        assert!(Instant::now() < deadline);
+        std::thread::sleep(Duration::from_millis(10));
+        records.extend(
+            ft.read_event()
+                .iter()
+                .filter(|e| e.pid == pid)
+                .filter_map(|e| tracker.track(e)),
+        );
+    }
+    child.stdin.take().unwrap().write_all(b"\n").unwrap();
+    child.wait().unwrap();
+
+    let meta = std::fs::metadata(&sh).unwrap();
+    assert_eq!(records[0].kind, ExecKind::Exec);
+    assert_eq!(records[0].path, sh);
+    assert_eq!((records[0].dev, records[0].ino), (meta.dev(), meta.ino()));
+    assert_eq!(records[0].hash, Some(meta.len().to_ne_bytes().to_vec()));
+    assert_eq!(records[0].ppid, Some(std::process::id() as i32));
+    assert_eq!(records[0].uid, Some(unsafe { libc::getuid() }));
+    assert_eq!(
+        records[1].kind,
+        ExecKind::Script {
+            interpreter: sh.clone()
+        }
+    );
+    assert_eq!(records[1].path, script);
+    assert_eq!(records[1].ino, std::fs::metadata(&script).unwrap().ino());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn exec_tracker_mount_test() {
+    use fanotify::exec::{ExecKind, ExecTracker};
+    use fanotify::high_level::{FanotifyBuilder, FAN_OPEN, FAN_OPEN_EXEC};
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+    let dir = std::env::temp_dir().join("fanotify_exec_mount_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("script");
+    std::fs::write(&script, "read line\n").unwrap();
+    // The whole mount, so that the ELF interpreter of the shell is reported too.
+    let ft = FanotifyBuilder::new().nonblocking().register().unwrap();
+    ft.add_mountpoint(FAN_OPEN_EXEC | FAN_OPEN, "/").unwrap();
+    ft.add_mountpoint(FAN_OPEN_EXEC | FAN_OPEN, &dir).unwrap();
+    let mut tracker = ExecTracker::new();
+
+    let mut child = Command::new("/bin/sh")
+        .arg("script")
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let pid = child.id() as i32;
+    let mut records = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !records
+        .iter()
+        .any(|r: &fanotify::exec::ExecRecord| r.path == script)
+    {
+        assert!(Instant::now() < deadline);
+        std::thread::sleep(Duration::from_millis(10));
+        records.extend(
+            ft.read_event()
+                .iter()
+                .filter(|e| e.pid == pid)
+                .filter_map(|e| tracker.track(e)),
+        );
+    }
+    child.stdin.take().unwrap().write_all(b"\n").unwrap();
+    child.wait().unwrap();
+
+    let sh = std::fs::canonicalize("/bin/sh").unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].kind, ExecKind::Exec);
+    assert_eq!(records[0].path, sh);
+    assert_eq!(records[1].kind, ExecKind::Script { interpreter: sh });
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,101 @@
+#[test]
+fn parse_test() {
+    use fanotify::fdinfo::{parse, KernelMarkTarget};
+    use fanotify::registry::MarkKind;
+    let state = parse(
+        "pos:\t0\n\
+         flags:\t02004002\n\
+         mnt_id:\t17\n\
+         ino:\t26\n\
+         fanotify flags:7 event-flags:88000\n\
+         fanotify ino:16 sdev:fe00000 mflags:440 mask:0 ignored_mask:40000020 fhandle-bytes:8 fhandle-type:81 f_handle:1600000000000000\n\
+         fanotify sdev:fe00000 mflags:0 mask:10000 ignored_mask:0\n\
+         fanotify mnt_id:1c mflags:0 mask:20 ignored_mask:0\n\
+         fanotify mnt_ns:4026531832 mflags:0 mask:3000000 ignored_mask:0\n",
+    )
+    .unwrap();
+    assert_eq!(state.flags, 0x7);
+    assert_eq!(state.event_flags, 0x88000);
+    assert_eq!(state.marks.len(), 4);
+    let inode = &state.marks[0];
+    assert_eq!(inode.kind(), MarkKind::Inode);
+    assert_eq!(inode.mflags, 0x440);
+    assert_eq!(inode.ignored_mask, 0x4000_0020);
+    assert_eq!(inode.dev(), Some(libc::makedev(254, 0)));
+    match &inode.target {
+        KernelMarkTarget::Inode { ino, handle, .. } => {
+            assert_eq!(*ino, 0x16);
+            assert_eq!(handle.as_ref().unwrap().handle_type, 0x81);
+            assert_eq!(handle.as_ref().unwrap().bytes, [0x16, 0, 0, 0, 0, 0, 0, 0]);
+        }
+        target => panic!("unexpected target {:?}", target),
+    }
+    assert_eq!(state.marks[1].kind(), MarkKind::Filesystem);
+    assert_eq!(state.marks[1].mask, 0x10000);
+    assert_eq!(state.marks[2].target, KernelMarkTarget::Mount { mnt_id: 0x1c });
+    assert_eq!(
+        state.marks[3].target,
+        KernelMarkTarget::MountNamespace { ns: 4026531832 }
+    );
+    assert!(parse("pos:\t0\nflags:\t02\n").is_none());
+}
+
+#[test]
+fn kernel_state_test() {
+    use fanotify::fdinfo::MarkDrift;
+    use fanotify::high_level::{Fanotify, FanotifyMode, FAN_CLOSE_WRITE, FAN_OPEN};
+    use fanotify::low_level::{fanotify_mark, AT_FDCWD, FAN_MARK_ADD};
+    let ft = Fanotify::new_nonblocking(FanotifyMode::NOTIF).unwrap();
+    ft.add_path(FAN_OPEN, "/tmp").unwrap();
+    ft.add_mountpoint(FAN_CLOSE_WRITE, "/tmp").unwrap();
+    ft.add_filesystem(FAN_CLOSE_WRITE, "/tmp").unwrap();
+    let state = ft.kernel_state().unwrap();
+    assert_eq!(state.marks.len(), 3);
+    for mark in ft.marks() {
+        assert_eq!(state.find(&mark).unwrap().mask, mark.mask);
+    }
+    assert!(ft.drift().unwrap().is_empty());
+    fanotify_mark(ft.as_raw_fd(), FAN_MARK_ADD, FAN_OPEN, AT_FDCWD, "/").unwrap();
+    let drift = ft.drift().unwrap();
+    assert_eq!(drift.len(), 1);
+    assert!(matches!(drift[0], MarkDrift::Unexpected(_)));
+
+    // The evictable marks of `cache_clean` aren't drift.
+    let file = std::fs::File::open("/proc/self/exe").unwrap();
+    let ft = Fanotify::new_nonblocking(FanotifyMode::CONTENT).unwrap();
+    ft.cache_clean(&file).unwrap();
+    assert_eq!(ft.kernel_state().unwrap().marks.len(), 1);
+    assert!(ft.drift().unwrap().is_empty());
+
+    // Nor is the kernel dropping FAN_EVENT_ON_CHILD from mount and file marks, or adding it
+    // back for groups reporting directory fids.
+    use fanotify::high_level::{FanotifyBuilder, FAN_EVENT_ON_CHILD};
+    let ft = Fanotify::new_nonblocking(FanotifyMode::NOTIF).unwrap();
+    ft.add_mountpoint(FAN_OPEN | FAN_EVENT_ON_CHILD, "/tmp").unwrap();
+    ft.add_path(FAN_OPEN | FAN_EVENT_ON_CHILD, "/proc/self/exe").unwrap();
+    assert!(ft.drift().unwrap().is_empty());
+    let ft = FanotifyBuilder::new().report_dir_fid().nonblocking().register().unwrap();
+    ft.add_mountpoint(FAN_OPEN, "/tmp").unwrap();
+    assert!(ft.drift().unwrap().is_empty());
+}
+
+#[test]
+fn scan_groups_test() {
+    use fanotify::fdinfo::{process_groups, scan_groups};
+    use fanotify::high_level::{Fanotify, FanotifyMode, FAN_OPEN};
+    let ft = Fanotify::new_nonblocking(FanotifyMode::NOTIF).unwrap();
+    ft.add_path(FAN_OPEN, "/tmp").unwrap();
+    let pid = std::process::id() as i32;
+    let groups = process_groups(pid).unwrap();
+    let group = groups
+        .iter()
+        .find(|group| group.fd == ft.as_raw_fd())
+        .unwrap();
+    assert_eq!(group.exe, std::env::current_exe().ok());
+    assert_eq!(group.state.class(), FanotifyMode::NOTIF);
+    assert!(group.state.marks.iter().any(|mark| mark.mask == FAN_OPEN));
+    assert!(scan_groups()
+        .unwrap()
+        .iter()
+        .any(|group| group.pid == pid && group.fd == ft.as_raw_fd()));
+}
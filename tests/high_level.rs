@@ -20,4 +20,539 @@ fn high_level_test() {
         assert_eq!(res, "xxx".to_string());
     });
     handler.join().unwrap();
-}
\ No newline at end of file
+}
+#[test]
+fn mark_error_test() {
+    use fanotify::error::Call;
+    use fanotify::high_level::{Fanotify, FanotifyMode, FAN_OPEN};
+    use fanotify::FanotifyError;
+    let ft = Fanotify::new_blocking(FanotifyMode::NOTIF).expect("Error regitering fanotify listener");
+    let err = ft
+        .add_path(FAN_OPEN, "/tmp/fanotify_test_missing/file")
+        .unwrap_err();
+    assert!(matches!(err, FanotifyError::Os { .. }));
+    assert_eq!(err.raw_os_error(), Some(libc::ENOENT));
+    match err.call() {
+        Call::Mark { path, .. } => assert_eq!(
+            path.as_deref(),
+            Some(std::path::Path::new("/tmp/fanotify_test_missing/file"))
+        ),
+        call => panic!("unexpected call {:?}", call),
+    }
+    let err = ft.add_path(0, "/tmp").unwrap_err();
+    assert!(matches!(err, FanotifyError::Unsupported { .. }));
+    let err: std::io::Error = err.into();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(err.raw_os_error(), Some(libc::EINVAL));
+    let err: std::io::Error = ft
+        .add_path(FAN_OPEN, "/tmp/fanotify_test_missing")
+        .unwrap_err()
+        .into();
+    assert_eq!(err.raw_os_error(), Some(libc::ENOENT));
+}
+
+#[test]
+fn validation_test() {
+    use fanotify::high_level::{
+        Fanotify, FanotifyBuilder, FanotifyMode, FAN_ATTRIB, FAN_CREATE, FAN_OPEN_PERM,
+    };
+    use fanotify::low_level::FAN_REPORT_NAME;
+    use fanotify::FanotifyError;
+    let err = FanotifyBuilder::new()
+        .with_flags(FAN_REPORT_NAME)
+        .register()
+        .err()
+        .unwrap();
+    assert!(matches!(err, FanotifyError::InvalidCombination { .. }));
+    let ft = Fanotify::new_blocking(FanotifyMode::NOTIF).unwrap();
+    assert!(matches!(
+        ft.add_path(FAN_OPEN_PERM, "/tmp"),
+        Err(FanotifyError::InvalidCombination { .. })
+    ));
+    assert!(matches!(
+        ft.add_mountpoint(FAN_CREATE, "/tmp"),
+        Err(FanotifyError::InvalidCombination { .. })
+    ));
+    assert!(matches!(
+        ft.add_mountpoint(FAN_ATTRIB, "/tmp"),
+        Err(FanotifyError::InvalidCombination { .. })
+    ));
+    ft.flush_path(0, "/tmp").unwrap();
+    let ft = Fanotify::new_blocking(FanotifyMode::CONTENT).unwrap();
+    ft.add_path(FAN_OPEN_PERM, "/tmp").unwrap();
+}
+
+#[test]
+fn builder_test() {
+    use fanotify::high_level::{EventAccess, FanEvent, FanotifyBuilder, FAN_CREATE};
+    use fanotify::low_level::{FAN_CLOEXEC, FAN_NONBLOCK, FAN_NOFD, FAN_REPORT_FID, O_LARGEFILE};
+    let ft = FanotifyBuilder::new()
+        .report_fid()
+        .nonblocking()
+        .with_event_access(EventAccess::ReadOnly)
+        .large_file()
+        .register()
+        .unwrap();
+    let config = ft.config().unwrap();
+    assert!(config.has_flag(FAN_REPORT_FID | FAN_NONBLOCK));
+    assert_eq!(config.event_flags() & O_LARGEFILE as u32, O_LARGEFILE as u32);
+    // Raw flags add to the named ones, whatever the order.
+    assert!(FanotifyBuilder::new()
+        .report_fid()
+        .with_flags(FAN_NONBLOCK)
+        .has_flag(FAN_REPORT_FID | FAN_NONBLOCK | FAN_CLOEXEC));
+    assert!(FanotifyBuilder::new()
+        .with_flags(FAN_NONBLOCK)
+        .report_fid()
+        .has_flag(FAN_REPORT_FID | FAN_NONBLOCK | FAN_CLOEXEC));
+
+    let dir = "/tmp/fanotify_builder_test";
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir(dir).unwrap();
+    ft.add_path(FAN_CREATE, dir).unwrap();
+    std::fs::File::create(format!("{}/file", dir)).unwrap();
+    let events = ft.read_event();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].fd, FAN_NOFD);
+    assert_eq!(events[0].events, vec![FanEvent::Create]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn filesystem_test() {
+    use fanotify::high_level::{Fanotify, FanotifyMode, FAN_CLOSE_WRITE, FAN_OPEN};
+    let ft = Fanotify::new_nonblocking(FanotifyMode::NOTIF).unwrap();
+    ft.add_filesystem(FAN_CLOSE_WRITE, "/tmp").unwrap();
+    ft.add_mountpoint(FAN_OPEN, "/tmp").unwrap();
+    std::fs::write("/tmp/fanotify_filesystem_test", b"xxx").unwrap();
+    assert!(ft
+        .read_event()
+        .iter()
+        .any(|event| event.path == "/tmp/fanotify_filesystem_test"));
+    ft.remove_filesystem(FAN_CLOSE_WRITE, "/tmp").unwrap();
+    ft.flush_mounts().unwrap();
+    ft.flush_filesystems().unwrap();
+    ft.read_event();
+    std::fs::write("/tmp/fanotify_filesystem_test", b"xxx").unwrap();
+    assert!(ft.read_event().is_empty());
+}
+
+#[test]
+fn ignore_test() {
+    use fanotify::high_level::{
+        FanEvent, Fanotify, FanotifyMode, IgnoreOptions, FAN_CLOSE_WRITE, FAN_ONDIR, FAN_OPEN,
+    };
+    let dir = "/tmp/fanotify_ignore_test";
+    let file = "/tmp/fanotify_ignore_test/file";
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir(dir).unwrap();
+    std::fs::write(file, b"xxx").unwrap();
+    let ft = Fanotify::new_nonblocking(FanotifyMode::NOTIF).unwrap();
+    ft.add_mountpoint(FAN_CLOSE_WRITE | FAN_OPEN | FAN_ONDIR, dir).unwrap();
+    ft.ignore_path(
+        FAN_CLOSE_WRITE,
+        file,
+        IgnoreOptions {
+            survive_modify: true,
+            ..IgnoreOptions::default()
+        },
+    )
+    .unwrap();
+    ft.ignore_path(
+        FAN_OPEN,
+        dir,
+        IgnoreOptions {
+            survive_modify: true,
+            ondir: true,
+            ..IgnoreOptions::default()
+        },
+    )
+    .unwrap();
+    std::fs::write(file, b"xxx").unwrap();
+    std::fs::read_dir(dir).unwrap();
+    let events = ft.read_event();
+    assert!(!events
+        .iter()
+        .any(|event| event.path == file && event.events.contains(&FanEvent::CloseWrite)));
+    assert!(!events.iter().any(|event| event.path == dir));
+    ft.unignore_path(FAN_CLOSE_WRITE, file).unwrap();
+    ft.unignore_path(FAN_OPEN | FAN_ONDIR, dir).unwrap();
+    std::fs::write(file, b"xxx").unwrap();
+    std::fs::read_dir(dir).unwrap();
+    let events = ft.read_event();
+    assert!(events.iter().any(|event| event.path == file));
+    assert!(events.iter().any(|event| event.path == dir));
+
+    // Directories, mounts and filesystems only take FAN_MARK_IGNORE if the ignore mask
+    // survives modifications, which is implied.
+    let ondir = IgnoreOptions {
+        ondir: true,
+        ..IgnoreOptions::default()
+    };
+    let ft = Fanotify::new_nonblocking(FanotifyMode::NOTIF).unwrap();
+    ft.add_mountpoint(FAN_OPEN | FAN_ONDIR, dir).unwrap();
+    ft.ignore_path(FAN_OPEN, dir, ondir).unwrap();
+    ft.ignore_mountpoint(FAN_CLOSE_WRITE, dir, ondir).unwrap();
+    ft.ignore_filesystem(FAN_CLOSE_WRITE, dir, ondir).unwrap();
+    assert!(ft.marks().iter().all(|mark| mark.ignore_options == ondir));
+    std::fs::write(file, b"xxx").unwrap();
+    std::fs::read_dir(dir).unwrap();
+    assert!(!ft.read_event().iter().any(|event| event.path == dir));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn fd_mark_test() {
+    use fanotify::high_level::{Fanotify, FanotifyMode, LookupOptions, FAN_CLOSE_WRITE, FAN_OPEN};
+    use std::os::unix::fs::symlink;
+    let dir = "/tmp/fanotify_fd_mark_test";
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir(dir).unwrap();
+    std::fs::write(format!("{}/file", dir), b"xxx").unwrap();
+    symlink("file", format!("{}/link", dir)).unwrap();
+    let ft = Fanotify::new_nonblocking(FanotifyMode::NOTIF).unwrap();
+    let file = std::fs::File::open(format!("{}/file", dir)).unwrap();
+    ft.add_fd(FAN_CLOSE_WRITE, &file).unwrap();
+    let dirfd = std::fs::File::open(dir).unwrap();
+    let only_dir = LookupOptions {
+        dont_follow: true,
+        only_dir: true,
+    };
+    assert_eq!(
+        ft.add_at(FAN_OPEN, &dirfd, "link", only_dir)
+            .unwrap_err()
+            .raw_os_error(),
+        Some(libc::ENOTDIR)
+    );
+    std::fs::write(format!("{}/file", dir), b"xxx").unwrap();
+    let events = ft.read_event();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].path, format!("{}/file", dir));
+    ft.remove_fd(FAN_CLOSE_WRITE, &file).unwrap();
+    ft.add_at(FAN_CLOSE_WRITE, &dirfd, "link", LookupOptions::default())
+        .unwrap();
+    std::fs::write(format!("{}/file", dir), b"xxx").unwrap();
+    assert_eq!(ft.read_event().len(), 1);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn cache_clean_test() {
+    use fanotify::high_level::{
+        Fanotify, FanotifyMode, FanotifyResponse, FAN_EVENT_ON_CHILD, FAN_OPEN_PERM,
+    };
+    use std::sync::mpsc::channel;
+    let dir = "/tmp/fanotify_cache_clean_test";
+    let file = "/tmp/fanotify_cache_clean_test/file";
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir(dir).unwrap();
+    std::fs::write(file, b"xxx").unwrap();
+    let ft = Fanotify::new_blocking(FanotifyMode::CONTENT).unwrap();
+    ft.add_path(FAN_OPEN_PERM | FAN_EVENT_ON_CHILD, dir).unwrap();
+
+    let (opened, rx) = channel();
+    let opener = std::thread::spawn(move || {
+        for _ in 0..3 {
+            std::fs::File::open(file).unwrap();
+            opened.send(()).unwrap();
+        }
+        std::fs::write(file, b"yyy").unwrap();
+        std::fs::File::open(file).unwrap();
+        opened.send(()).unwrap();
+    });
+    let events = ft.read_event();
+    assert_eq!(events.len(), 1);
+    ft.cache_clean(&events[0].try_as_fd().unwrap()).unwrap();
+    ft.send_response(events[0].fd, FanotifyResponse::Allow);
+    for _ in 0..3 {
+        rx.recv().unwrap();
+    }
+    // The modification cleared the ignore mask, so the last open asks again.
+    for event in ft.read_event() {
+        assert_eq!(event.path, file);
+        ft.send_response(event.fd, FanotifyResponse::Allow);
+    }
+    rx.recv().unwrap();
+    opener.join().unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn mount_namespace_test() {
+    use fanotify::high_level::{
+        FanotifyBuilder, MountEvent, FAN_MNT_ATTACH, FAN_MNT_DETACH, FAN_OPEN,
+    };
+    use fanotify::registry::MarkKind;
+    use fanotify::FanotifyError;
+    use std::ffi::CString;
+    let ns = std::fs::File::open("/proc/self/ns/mnt").unwrap();
+    let err = FanotifyBuilder::new()
+        .report_mnt()
+        .report_fid()
+        .register()
+        .err()
+        .unwrap();
+    assert!(matches!(err, FanotifyError::InvalidCombination { .. }));
+    let ft = FanotifyBuilder::new().nonblocking().register().unwrap();
+    assert!(matches!(
+        ft.add_mount_namespace(FAN_MNT_ATTACH, &ns),
+        Err(FanotifyError::InvalidCombination { .. })
+    ));
+
+    let ft = FanotifyBuilder::new()
+        .report_mnt()
+        .nonblocking()
+        .register()
+        .unwrap();
+    assert!(matches!(
+        ft.add_mount_namespace(FAN_OPEN, &ns),
+        Err(FanotifyError::InvalidCombination { .. })
+    ));
+    ft.add_mount_namespace(FAN_MNT_ATTACH | FAN_MNT_DETACH, &ns)
+        .unwrap();
+    let marks = ft.marks();
+    assert_eq!(marks.len(), 1);
+    assert_eq!(marks[0].kind, MarkKind::MountNamespace);
+    assert!(ft.drift().unwrap().is_empty());
+
+    let dir = "/tmp/fanotify_mount_namespace_test";
+    let _ = std::fs::create_dir(dir);
+    let target = CString::new(dir).unwrap();
+    let fstype = CString::new("tmpfs").unwrap();
+    let ret = unsafe {
+        libc::mount(
+            fstype.as_ptr(),
+            target.as_ptr(),
+            fstype.as_ptr(),
+            0,
+            std::ptr::null(),
+        )
+    };
+    assert_eq!(ret, 0);
+    assert_eq!(unsafe { libc::umount(target.as_ptr()) }, 0);
+    // Other processes may mount in the namespace too, so look for a matching pair.
+    let events: Vec<MountEvent> = ft
+        .read_event()
+        .iter()
+        .filter_map(|e| e.mount_event())
+        .collect();
+    assert!(events
+        .iter()
+        .any(|attach| matches!(attach, MountEvent::Attach { .. })
+            && events.contains(&MountEvent::Detach {
+                mnt_id: attach.mnt_id()
+            })));
+
+    // Namespaces have no path, so their marks keep a descriptor to be removed or restored.
+    let restored = FanotifyBuilder::new()
+        .report_mnt()
+        .nonblocking()
+        .register()
+        .unwrap();
+    restored.restore(&ft.snapshot()).unwrap();
+    assert_eq!(restored.marks().len(), 1);
+    assert!(restored.drift().unwrap().is_empty());
+    restored.remove_mark(restored.marks()[0].id).unwrap();
+    assert!(restored.marks().is_empty());
+    assert!(restored.kernel_state().unwrap().marks.is_empty());
+
+    ft.remove_mount_namespace(FAN_MNT_ATTACH | FAN_MNT_DETACH, &ns)
+        .unwrap();
+    assert!(ft.marks().is_empty());
+    std::fs::remove_dir(dir).unwrap();
+}
+
+#[test]
+fn unreachable_mark_test() {
+    use fanotify::high_level::{Fanotify, FanotifyMode, FAN_CLOSE_WRITE};
+    use fanotify::FanotifyError;
+    use std::os::fd::{FromRawFd, OwnedFd};
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let (read, _write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    let ft = Fanotify::new_nonblocking(FanotifyMode::NOTIF).unwrap();
+    ft.add_fd(FAN_CLOSE_WRITE, &read).unwrap();
+    let marks = ft.marks();
+    assert_eq!(marks.len(), 1);
+    assert!(marks[0].path.is_none());
+    assert!(matches!(
+        ft.remove_mark(marks[0].id),
+        Err(FanotifyError::UnreachableMark { .. })
+    ));
+}
+
+#[test]
+fn pidfd_test() {
+    use fanotify::high_level::{EventInfo, FanotifyBuilder, FAN_OPEN};
+    use fanotify::FanotifyError;
+    use std::os::fd::AsRawFd;
+    let err = FanotifyBuilder::new()
+        .report_pidfd()
+        .report_tid()
+        .register()
+        .err()
+        .unwrap();
+    assert!(matches!(err, FanotifyError::InvalidCombination { .. }));
+
+    let dir = std::env::temp_dir().join("fanotify_pidfd_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("file");
+    std::fs::write(&file, b"x").unwrap();
+    let ft = FanotifyBuilder::new()
+        .report_pidfd()
+        .nonblocking()
+        .register()
+        .unwrap();
+    ft.add_path(FAN_OPEN, &file).unwrap();
+    std::fs::File::open(&file).unwrap();
+    let events = ft.read_event();
+    let event = events
+        .iter()
+        .find(|e| e.pid == std::process::id() as i32)
+        .unwrap();
+    assert!(event.info.contains(&EventInfo::Pidfd));
+    let pidfd = event.pidfd().unwrap();
+    let fdinfo =
+        std::fs::read_to_string(format!("/proc/self/fdinfo/{}", pidfd.as_raw_fd())).unwrap();
+    assert!(fdinfo.lines().any(|line| line
+        .split_whitespace()
+        .eq(["Pid:", &std::process::id().to_string()])));
+    // Clones own their own pidfd.
+    let clone = event.try_clone().unwrap();
+    drop(events);
+    assert!(clone.pidfd().is_some());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+#[test]
+fn deleted_test() {
+    use fanotify::high_level::{FanotifyBuilder, FAN_EVENT_ON_CHILD, FAN_OPEN};
+    let dir = std::env::temp_dir().join("fanotify_deleted_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let ft = FanotifyBuilder::new().nonblocking().register().unwrap();
+    ft.add_path(FAN_OPEN | FAN_EVENT_ON_CHILD, &dir).unwrap();
+    let pid = std::process::id() as i32;
+
+    let deleted = dir.join("file");
+    std::fs::write(&deleted, b"x").unwrap();
+    // A file whose name ends like the suffix the kernel gives to deleted files.
+    let misleading = dir.join("other (deleted)");
+    std::fs::write(&misleading, b"x").unwrap();
+    let _ = ft.read_event();
+    std::fs::File::open(&deleted).unwrap();
+    std::fs::File::open(&misleading).unwrap();
+    std::fs::remove_file(&deleted).unwrap();
+
+    let events = ft.read_event();
+    let events: Vec<_> = events.iter().filter(|e| e.pid == pid).collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].path, deleted.to_str().unwrap());
+    assert!(events[0].is_deleted());
+    assert_eq!(events[1].path, misleading.to_str().unwrap());
+    assert!(!events[1].is_deleted());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+#[test]
+fn stat_test() {
+    use fanotify::high_level::{FanotifyBuilder, FAN_EVENT_ON_CHILD, FAN_ONDIR, FAN_OPEN};
+    use std::io::Write;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    let dir = std::env::temp_dir().join("fanotify_stat_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("file");
+    std::fs::write(&file, b"xxx").unwrap();
+    std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let pid = std::process::id() as i32;
+    for builder in [
+        FanotifyBuilder::new().stat_on_read(),
+        FanotifyBuilder::new(),
+    ] {
+        let ft = builder.nonblocking().register().unwrap();
+        ft.add_path(FAN_OPEN | FAN_ONDIR | FAN_EVENT_ON_CHILD, &dir)
+            .unwrap();
+        std::fs::File::open(&file).unwrap();
+        std::fs::read_dir(&dir).unwrap();
+        let events = ft.read_event();
+        let events: Vec<_> = events.iter().filter(|e| e.pid == pid).collect();
+        assert_eq!(events.len(), 2);
+        // Snapshots taken at read time don't see later changes.
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&file)
+            .unwrap()
+            .write_all(b"x")
+            .unwrap();
+        let stat = events[0].stat().unwrap();
+        let meta = std::fs::metadata(&file).unwrap();
+        assert_eq!((stat.dev, stat.ino), (meta.dev(), meta.ino()));
+        assert_eq!(stat.mode & 0o777, 0o755);
+        assert_eq!(stat.uid, meta.uid());
+        let expected = if builder.has_stat_on_read() { 3 } else { 4 };
+        assert_eq!(stat.size, expected);
+        assert_eq!(events[0].stat().unwrap().size, expected);
+        assert!(events[0].is_regular() && events[0].is_executable());
+        assert!(!events[0].is_dir());
+        assert!(events[1].is_dir() && !events[1].is_executable());
+        std::fs::write(&file, b"xxx").unwrap();
+    }
+
+    // 1.7s before the epoch.
+    let times = [libc::timespec {
+        tv_sec: -2,
+        tv_nsec: 300_000_000,
+    }; 2];
+    let path = std::ffi::CString::new(file.to_str().unwrap()).unwrap();
+    assert_eq!(
+        unsafe { libc::utimensat(libc::AT_FDCWD, path.as_ptr(), times.as_ptr(), 0) },
+        0
+    );
+    let ft = FanotifyBuilder::new().nonblocking().register().unwrap();
+    ft.add_path(FAN_OPEN, &file).unwrap();
+    std::fs::File::open(&file).unwrap();
+    let events = ft.read_event();
+    let event = events.iter().find(|e| e.pid == pid).unwrap();
+    assert_eq!(
+        event.stat().unwrap().mtime,
+        std::time::UNIX_EPOCH - std::time::Duration::from_millis(1700)
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn read_at_test() {
+    use fanotify::high_level::{FanotifyBuilder, FAN_ACCESS, FAN_OPEN};
+    use std::io::{Read, Seek, SeekFrom};
+    let dir = std::env::temp_dir().join("fanotify_read_at_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("file");
+    std::fs::write(&file, b"\x7fELF and the rest").unwrap();
+    let pid = std::process::id() as i32;
+    let ft = FanotifyBuilder::new().nonblocking().register().unwrap();
+    ft.add_path(FAN_OPEN | FAN_ACCESS, &file).unwrap();
+    std::fs::File::open(&file).unwrap();
+    let events = ft.read_event();
+    let event = events.iter().find(|e| e.pid == pid).unwrap();
+
+    let mut buf = [0; 3];
+    assert_eq!(event.read_at(&mut buf, 5).unwrap(), 3);
+    assert_eq!(&buf, b"and");
+    assert_eq!(event.read_prefix(4).unwrap(), b"\x7fELF");
+    assert_eq!(event.read_prefix(usize::MAX).unwrap(), b"\x7fELF and the rest");
+
+    let mut reader = event.reader().unwrap();
+    let mut contents = String::new();
+    reader.seek(SeekFrom::Start(1)).unwrap();
+    reader.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "ELF and the rest");
+    assert_eq!(reader.seek(SeekFrom::End(-4)).unwrap(), 13);
+    assert_eq!(reader.seek(SeekFrom::Current(-1)).unwrap(), 12);
+    assert!(reader.seek(SeekFrom::Current(-13)).is_err());
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b" rest");
+
+    // The offset of the event fd didn't move, and no access events were generated.
+    assert_eq!(unsafe { libc::lseek(event.fd, 0, libc::SEEK_CUR) }, 0);
+    drop(reader);
+    assert!(ft.read_event().iter().all(|e| e.pid != pid));
+    std::fs::remove_dir_all(dir).unwrap();
+}
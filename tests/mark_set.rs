@@ -0,0 +1,44 @@
+#[test]
+fn mark_set_test() {
+    use fanotify::high_level::{
+        Fanotify, FanotifyMode, IgnoreOptions, FAN_CLOSE_WRITE, FAN_MODIFY, FAN_OPEN,
+    };
+    use fanotify::mark_set::MarkSet;
+    let dir = "/tmp/fanotify_mark_set_test";
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir(dir).unwrap();
+    let ft = Fanotify::new_nonblocking(FanotifyMode::NOTIF).unwrap();
+    ft.add_path(FAN_OPEN, dir).unwrap();
+    let before = ft.marks();
+
+    let err = MarkSet::new()
+        .add_path(FAN_OPEN | FAN_CLOSE_WRITE, dir)
+        .add_mountpoint(FAN_MODIFY, dir)
+        .ignore_path(FAN_MODIFY, dir, IgnoreOptions::default())
+        .add_path(FAN_OPEN, "/tmp/fanotify_mark_set_test/missing")
+        .apply(&ft)
+        .unwrap_err();
+    assert_eq!(err.index, 3);
+    assert_eq!(err.source.raw_os_error(), Some(libc::ENOENT));
+    assert!(err.rollback_errors.is_empty());
+    assert_eq!(ft.marks(), before);
+    assert!(ft.drift().unwrap().is_empty());
+
+    MarkSet::new()
+        .add_path(FAN_CLOSE_WRITE, dir)
+        .add_mountpoint(FAN_MODIFY, dir)
+        .apply(&ft)
+        .unwrap();
+    assert_eq!(ft.marks().len(), 2);
+
+    let before = ft.marks();
+    let err = MarkSet::new()
+        .remove_path(FAN_OPEN, dir)
+        .unignore_path(FAN_MODIFY, dir)
+        .add_path(FAN_OPEN, "/tmp/fanotify_mark_set_test/missing")
+        .apply(&ft)
+        .unwrap_err();
+    assert_eq!(err.index, 2);
+    assert_eq!(ft.marks(), before);
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,266 @@
+#[test]
+fn parse_test() {
+    use fanotify::mountinfo::parse;
+    let mounts = parse(
+        "23 28 0:22 / /proc rw,relatime - proc proc rw\n\
+         43 28 0:39 /sub /tmp/a\\040b\\134c rw,relatime shared:1 master:2 - tmpfs none rw,size=10k\n\
+         garbage\n",
+    );
+    assert_eq!(mounts.len(), 2);
+    assert_eq!(mounts[0].fstype, "proc");
+    assert_eq!(mounts[1].parent_id, 28);
+    assert_eq!(mounts[1].dev, libc::makedev(0, 39));
+    assert_eq!(mounts[1].root, std::path::Path::new("/sub"));
+    assert_eq!(mounts[1].mount_point, std::path::Path::new("/tmp/a b\\c"));
+    assert_eq!(mounts[1].source, "none");
+    assert_eq!(mounts[1].super_options, "rw,size=10k");
+
+    let own = fanotify::mountinfo::read().unwrap();
+    assert!(own
+        .iter()
+        .any(|mount| mount.mount_point == std::path::Path::new("/")));
+}
+
+#[test]
+fn mount_watcher_test() {
+    use fanotify::high_level::{Fanotify, FanotifyMode, FAN_CLOSE_WRITE};
+    use fanotify::mountinfo::{MountChange, MountFilter, MountWatcher};
+    use fanotify::registry::MarkKind;
+    use std::ffi::CString;
+    use std::path::Path;
+    let dir = "/tmp/fanotify_mount_watcher_test";
+    let _ = std::fs::create_dir(dir);
+    let ft = Fanotify::new_nonblocking(FanotifyMode::NOTIF).unwrap();
+    let filter = MountFilter::new().fstype("tmpfs").path_prefix(dir);
+    let mut watcher = MountWatcher::new(&ft, MarkKind::Mount, FAN_CLOSE_WRITE, filter).unwrap();
+    // Other tests mount and unmount concurrently, only look at the changes of `dir`.
+    let ours = |changes: Vec<MountChange>| -> Vec<MountChange> {
+        changes
+            .into_iter()
+            .filter(|change| match change {
+                MountChange::Mounted { mount, .. }
+                | MountChange::MarkFailed { mount, .. }
+                | MountChange::Unmounted { mount, .. } => mount.mount_point == Path::new(dir),
+            })
+            .collect()
+    };
+    assert!(ours(watcher.poll(0).unwrap()).is_empty());
+
+    let target = CString::new(dir).unwrap();
+    let source = CString::new("fanotify_test").unwrap();
+    let fstype = CString::new("tmpfs").unwrap();
+    let ret = unsafe {
+        libc::mount(
+            source.as_ptr(),
+            target.as_ptr(),
+            fstype.as_ptr(),
+            0,
+            std::ptr::null(),
+        )
+    };
+    assert_eq!(ret, 0);
+    let changes = ours(watcher.poll(1000).unwrap());
+    assert!(matches!(
+        changes.as_slice(),
+        [MountChange::Mounted { marked: true, .. }]
+    ));
+    let marks = ft.marks();
+    assert_eq!(marks.len(), 1);
+    assert_eq!(marks[0].kind, MarkKind::Mount);
+    assert!(ft.drift().unwrap().is_empty());
+
+    assert_eq!(unsafe { libc::umount(target.as_ptr()) }, 0);
+    let changes = ours(watcher.poll(1000).unwrap());
+    assert!(matches!(
+        changes.as_slice(),
+        [MountChange::Unmounted {
+            was_marked: true,
+            ..
+        }]
+    ));
+    assert!(ft.marks().is_empty());
+    std::fs::remove_dir(dir).unwrap();
+}
+
+#[test]
+fn mount_tree_test() {
+    use fanotify::high_level::{Fanotify, FanotifyMode, FAN_CLOSE_WRITE, FAN_CREATE};
+    use fanotify::mountinfo::{MountFilter, MountTreeError};
+    use fanotify::registry::MarkKind;
+    use std::ffi::CString;
+    let mount = |fstype: &str, target: &str| {
+        let target = CString::new(target).unwrap();
+        let fstype = CString::new(fstype).unwrap();
+        let ret = unsafe {
+            libc::mount(
+                fstype.as_ptr(),
+                target.as_ptr(),
+                fstype.as_ptr(),
+                0,
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(ret, 0);
+    };
+    let umount = |target: &str| {
+        let target = CString::new(target).unwrap();
+        assert_eq!(unsafe { libc::umount(target.as_ptr()) }, 0);
+    };
+    let dir = "/tmp/fanotify_mount_tree_test";
+    let _ = std::fs::create_dir(dir);
+    mount("tmpfs", dir);
+    std::fs::create_dir(format!("{}/sub", dir)).unwrap();
+    std::fs::create_dir(format!("{}/proc", dir)).unwrap();
+    mount("tmpfs", &format!("{}/sub", dir));
+    mount("proc", &format!("{}/proc", dir));
+
+    let ft = Fanotify::new_nonblocking(FanotifyMode::NOTIF).unwrap();
+    let marked = ft
+        .add_mount_tree(FAN_CLOSE_WRITE, dir, &MountFilter::new())
+        .unwrap();
+    let points: Vec<_> = marked.iter().map(|m| m.mount_point.clone()).collect();
+    assert_eq!(
+        points,
+        [
+            std::path::PathBuf::from(dir),
+            std::path::PathBuf::from(format!("{}/sub", dir))
+        ]
+    );
+    let marks = ft.marks();
+    assert_eq!(marks.len(), 2);
+    assert!(marks.iter().all(|m| m.kind == MarkKind::Mount));
+    assert!(ft.drift().unwrap().is_empty());
+
+    let ft = Fanotify::new_nonblocking(FanotifyMode::NOTIF).unwrap();
+    let marked = ft
+        .add_mount_tree(FAN_CLOSE_WRITE, dir, &MountFilter::new().include_pseudo())
+        .unwrap();
+    assert_eq!(marked.len(), 3);
+
+    // Inode events can't be used on mounts: the first mark fails and nothing is left marked.
+    let ft = Fanotify::new_nonblocking(FanotifyMode::NOTIF).unwrap();
+    match ft.add_mount_tree(FAN_CREATE, dir, &MountFilter::new()) {
+        Err(MountTreeError::Mark(err)) => assert_eq!(err.index, 0),
+        result => panic!("unexpected result {:?}", result),
+    }
+    assert!(ft.marks().is_empty());
+    assert!(matches!(
+        ft.add_mount_tree(FAN_CLOSE_WRITE, "/nonexistent", &MountFilter::new()),
+        Err(MountTreeError::Mountinfo(_))
+    ));
+
+    umount(&format!("{}/proc", dir));
+    umount(&format!("{}/sub", dir));
+    umount(dir);
+    std::fs::remove_dir(dir).unwrap();
+}
+#[test]
+fn process_path_test() {
+    use fanotify::high_level::{FanotifyBuilder, FAN_OPEN};
+    use fanotify::mountinfo::{path_in_process, ProcessPath};
+    use std::ffi::CString;
+    use std::io::Write;
+    use std::os::unix::process::CommandExt;
+    use std::path::PathBuf;
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+    let dir = PathBuf::from("/tmp/fanotify_process_path_test");
+    let alt = PathBuf::from("/tmp/fanotify_process_path_alt");
+    let _ = std::fs::create_dir(&dir);
+    let _ = std::fs::create_dir(&alt);
+    let c_dir = CString::new(dir.to_str().unwrap()).unwrap();
+    let c_alt = CString::new(alt.to_str().unwrap()).unwrap();
+    let tmpfs = CString::new("tmpfs").unwrap();
+    let ret = unsafe {
+        libc::mount(
+            tmpfs.as_ptr(),
+            c_dir.as_ptr(),
+            tmpfs.as_ptr(),
+            0,
+            std::ptr::null(),
+        )
+    };
+    assert_eq!(ret, 0);
+    let file = dir.join("file");
+    std::fs::write(&file, b"x").unwrap();
+    let pid = std::process::id() as i32;
+
+    let ft = FanotifyBuilder::new().nonblocking().register().unwrap();
+    ft.add_path(FAN_OPEN, &file).unwrap();
+    let opened = std::fs::File::open(&file).unwrap();
+    assert_eq!(
+        path_in_process(&opened, pid),
+        ProcessPath::Resolved(file.clone())
+    );
+    let events = ft.read_event();
+    let event = events.iter().find(|e| e.pid == pid).unwrap();
+    assert_eq!(
+        event.path_in_process_ns(),
+        ProcessPath::Resolved(file.clone())
+    );
+
+    // A process of another mount namespace, where the directory is bind mounted elsewhere.
+    let mut child = unsafe {
+        Command::new("sh")
+            .arg("-c")
+            .arg(format!("exec 3< {}/file; read line", alt.display()))
+            .stdin(Stdio::piped())
+            .pre_exec(move || {
+                let none = std::ptr::null();
+                if libc::unshare(libc::CLONE_NEWNS) != 0
+                    || libc::mount(
+                        none,
+                        c"/".as_ptr(),
+                        none,
+                        libc::MS_REC | libc::MS_PRIVATE,
+                        none as _,
+                    ) != 0
+                    || libc::mount(
+                        c_dir.as_ptr(),
+                        c_alt.as_ptr(),
+                        none,
+                        libc::MS_BIND,
+                        none as _,
+                    ) != 0
+                {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            })
+            .spawn()
+            .unwrap()
+    };
+    let child_pid = child.id() as i32;
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let event = loop {
+        assert!(Instant::now() < deadline);
+        std::thread::sleep(Duration::from_millis(10));
+        if let Some(event) = ft.read_event().into_iter().find(|e| e.pid == child_pid) {
+            break event;
+        }
+    };
+    assert_eq!(
+        event.path_in_process_ns(),
+        ProcessPath::Resolved(alt.join("file"))
+    );
+    child.stdin.take().unwrap().write_all(b"\n").unwrap();
+    child.wait().unwrap();
+
+    let deleted = dir.join("deleted");
+    std::fs::write(&deleted, b"x").unwrap();
+    let deleted_file = std::fs::File::open(&deleted).unwrap();
+    std::fs::remove_file(&deleted).unwrap();
+    assert_eq!(
+        path_in_process(&deleted_file, pid),
+        ProcessPath::Deleted(deleted)
+    );
+
+    let c_dir = CString::new(dir.to_str().unwrap()).unwrap();
+    assert_eq!(
+        unsafe { libc::umount2(c_dir.as_ptr(), libc::MNT_DETACH) },
+        0
+    );
+    assert_eq!(path_in_process(&opened, pid), ProcessPath::Detached);
+    std::fs::remove_dir(dir).unwrap();
+    std::fs::remove_dir(alt).unwrap();
+}
@@ -0,0 +1,321 @@
+#[test]
+fn read_test() {
+    use fanotify::process::ProcessInfo;
+    use std::os::fd::{FromRawFd, OwnedFd};
+    let pid = std::process::id() as i32;
+    let info = ProcessInfo::read(pid).unwrap();
+    assert_eq!(info.pid, pid);
+    assert_eq!(info.ppid, unsafe { libc::getppid() });
+    assert_eq!(info.uid, unsafe { libc::getuid() });
+    assert_eq!(info.euid, unsafe { libc::geteuid() });
+    assert_eq!(info.gid, unsafe { libc::getgid() });
+    assert_eq!(info.egid, unsafe { libc::getegid() });
+    assert_eq!(info.exe, Some(std::env::current_exe().unwrap()));
+    assert_eq!(info.cmdline, std::env::args().collect::<Vec<String>>());
+    assert!(info.is_running());
+    let cgroup = std::fs::read_to_string("/proc/self/cgroup").unwrap();
+    assert_eq!(
+        info.cgroup.as_deref(),
+        fanotify::container::unified_path(&cgroup)
+    );
+    let ns = |name: &str| {
+        std::os::unix::fs::MetadataExt::ino(
+            &std::fs::metadata(format!("/proc/self/ns/{}", name)).unwrap(),
+        )
+    };
+    assert_eq!(info.mnt_ns, Some(ns("mnt")));
+    assert_eq!(info.pid_ns, Some(ns("pid")));
+
+    let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) } as i32;
+    assert!(pidfd >= 0);
+    let pidfd = unsafe { OwnedFd::from_raw_fd(pidfd) };
+    assert_eq!(ProcessInfo::from_pidfd(&pidfd).unwrap(), info);
+}
+
+#[test]
+fn exited_test() {
+    use fanotify::process::ProcessInfo;
+    use std::io::ErrorKind;
+    let mut child = std::process::Command::new("true").spawn().unwrap();
+    let info = ProcessInfo::read(child.id() as i32).unwrap();
+    child.wait().unwrap();
+    assert!(!info.is_running());
+    assert_eq!(
+        ProcessInfo::read(info.pid).unwrap_err().kind(),
+        ErrorKind::NotFound
+    );
+}
+
+#[test]
+fn event_process_test() {
+    use fanotify::high_level::{FanotifyBuilder, FAN_OPEN};
+    let dir = std::env::temp_dir().join("fanotify_process_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("file");
+    std::fs::write(&file, b"x").unwrap();
+    let pid = std::process::id() as i32;
+    for builder in [
+        FanotifyBuilder::new().process_info(),
+        FanotifyBuilder::new().process_info().report_pidfd(),
+    ] {
+        let ft = builder.nonblocking().register().unwrap();
+        ft.add_path(FAN_OPEN, &file).unwrap();
+        std::fs::File::open(&file).unwrap();
+        let events = ft.read_event();
+        let event = events.iter().find(|e| e.pid == pid).unwrap();
+        let process = event.process.as_ref().unwrap();
+        assert_eq!(process.pid, pid);
+        assert_eq!(process.exe, Some(std::env::current_exe().unwrap()));
+    }
+
+    // Not opted in: the process is only read on demand.
+    let ft = FanotifyBuilder::new().nonblocking().register().unwrap();
+    ft.add_path(FAN_OPEN, &file).unwrap();
+    std::fs::File::open(&file).unwrap();
+    let events = ft.read_event();
+    let event = events.iter().find(|e| e.pid == pid).unwrap();
+    assert!(event.process.is_none());
+    assert_eq!(event.process_info().unwrap().pid, pid);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn cache_test() {
+    use fanotify::process::{ProcessCache, ProcessCacheConfig, ProcessCacheStats};
+    use std::os::fd::{AsFd, FromRawFd, OwnedFd};
+    use std::process::Command;
+    use std::time::Duration;
+    let pid = std::process::id() as i32;
+    let mut cache = ProcessCache::new(ProcessCacheConfig {
+        capacity: 2,
+        ttl: Duration::from_secs(60),
+    });
+    let info = cache.get(pid, None).unwrap();
+    assert_eq!(cache.get(pid, None).unwrap(), info);
+    assert_eq!(cache.stats(), ProcessCacheStats { hits: 1, misses: 1 });
+
+    // The least recently used process is evicted.
+    let mut first = Command::new("sleep").arg("60").spawn().unwrap();
+    let mut second = Command::new("sleep").arg("60").spawn().unwrap();
+    let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, second.id(), 0) } as i32;
+    assert!(pidfd >= 0);
+    let pidfd = unsafe { OwnedFd::from_raw_fd(pidfd) };
+    cache.get(first.id() as i32, None).unwrap();
+    cache.get(second.id() as i32, Some(pidfd.as_fd())).unwrap();
+    assert_eq!(cache.len(), 2);
+    assert!(cache.remove(pid).is_none());
+
+    // Exited processes are pruned.
+    cache.prune();
+    assert_eq!(cache.len(), 2);
+    first.kill().unwrap();
+    first.wait().unwrap();
+    second.kill().unwrap();
+    second.wait().unwrap();
+    cache.prune();
+    assert!(cache.is_empty());
+    assert!(cache.get(first.id() as i32, None).is_err());
+
+    // Entries older than the TTL are read again.
+    let mut cache = ProcessCache::new(ProcessCacheConfig {
+        capacity: 2,
+        ttl: Duration::ZERO,
+    });
+    cache.get(pid, None).unwrap();
+    cache.get(pid, None).unwrap();
+    assert_eq!(cache.stats(), ProcessCacheStats { hits: 0, misses: 2 });
+}
+
+#[test]
+fn event_cache_test() {
+    use fanotify::high_level::{FanotifyBuilder, FAN_EVENT_ON_CHILD, FAN_OPEN};
+    let dir = std::env::temp_dir().join("fanotify_process_cache_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let pid = std::process::id() as i32;
+    let ft = FanotifyBuilder::new()
+        .process_info()
+        .nonblocking()
+        .register()
+        .unwrap();
+    ft.add_path(FAN_OPEN | FAN_EVENT_ON_CHILD, &dir).unwrap();
+    // Distinct files, as the kernel merges identical events.
+    for i in 0..3 {
+        std::fs::write(dir.join(i.to_string()), b"x").unwrap();
+    }
+    let events = ft.read_event();
+    let opens: Vec<_> = events.iter().filter(|e| e.pid == pid).collect();
+    assert_eq!(opens.len(), 3);
+    assert!(opens.iter().all(|e| e.process.as_ref().unwrap().pid == pid));
+    let cache = ft.process_cache().unwrap();
+    assert_eq!(cache.stats().misses, 1);
+    assert!(cache.stats().hits >= 2);
+    drop(cache);
+
+    // Exited processes are pruned by later reads.
+    let mut child = std::process::Command::new("tail")
+        .arg("-f")
+        .arg(dir.join("0"))
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let child_pid = child.id() as i32;
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while !ft.read_event().iter().any(|e| e.pid == child_pid) {
+        assert!(std::time::Instant::now() < deadline);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert_eq!(ft.process_cache().unwrap().len(), 2);
+    child.kill().unwrap();
+    child.wait().unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    std::fs::write(dir.join("3"), b"x").unwrap();
+    assert!(!ft.read_event().is_empty());
+    assert_eq!(ft.process_cache().unwrap().len(), 1);
+    assert!(FanotifyBuilder::new()
+        .nonblocking()
+        .register()
+        .unwrap()
+        .process_cache()
+        .is_none());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn ancestors_test() {
+    use fanotify::process::{ProcessCache, ProcessCacheConfig};
+    let mut cache = ProcessCache::new(ProcessCacheConfig::default());
+    let ancestors = cache.ancestors(std::process::id() as i32);
+    let ppid = unsafe { libc::getppid() };
+    assert_eq!(ancestors[0].pid, ppid);
+    assert_eq!(
+        ancestors[0].exe,
+        std::fs::read_link(format!("/proc/{}/exe", ppid)).ok()
+    );
+    assert_eq!(ancestors.last().unwrap().pid, 1);
+    assert!(ancestors
+        .windows(2)
+        .all(|pair| pair[1].start_time <= pair[0].start_time));
+    assert!(cache.ancestors(-1).is_empty());
+}
+
+#[test]
+fn exec_tree_test() {
+    use fanotify::high_level::{FanotifyBuilder, FAN_EVENT_ON_CHILD, FAN_OPEN_EXEC};
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+    let dir = std::env::temp_dir().join("fanotify_exec_tree_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let (sh, sleep) = (dir.join("sh"), dir.join("sleep"));
+    std::fs::copy("/bin/sh", &sh).unwrap();
+    std::fs::copy("/bin/sleep", &sleep).unwrap();
+    let ft = FanotifyBuilder::new()
+        .process_ancestry()
+        .nonblocking()
+        .register()
+        .unwrap();
+    ft.add_path(FAN_OPEN_EXEC | FAN_EVENT_ON_CHILD, &dir)
+        .unwrap();
+
+    // The shell starts `sleep` in the background and exits once its stdin is closed, so that
+    // `sleep` is reparented.
+    let mut shell = Command::new(&sh)
+        .arg("-c")
+        .arg(format!("{} 60 & echo $!; read line", sleep.display()))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    std::io::BufRead::read_line(
+        &mut std::io::BufReader::new(shell.stdout.as_mut().unwrap()),
+        &mut line,
+    )
+    .unwrap();
+    let sleep_pid: i32 = line.trim().parse().unwrap();
+    let mut events = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !events
+        .iter()
+        .any(|e: &fanotify::high_level::Event| e.pid == sleep_pid)
+    {
+        assert!(Instant::now() < deadline);
+        std::thread::sleep(Duration::from_millis(10));
+        events.extend(ft.read_event());
+    }
+    let exec = events.iter().find(|e| e.pid == sleep_pid).unwrap();
+    assert_eq!(exec.ancestors()[0].pid, shell.id() as i32);
+    assert_eq!(exec.ancestors()[1].pid, std::process::id() as i32);
+
+    shell.stdin.take().unwrap().write_all(b"\n").unwrap();
+    shell.wait().unwrap();
+    let ancestors = ft.process_cache().unwrap().ancestors(sleep_pid);
+    assert_eq!(ancestors[0].pid, shell.id() as i32);
+    assert_eq!(ancestors[0].exe.as_ref(), Some(&sh));
+    assert_eq!(ancestors[1].pid, std::process::id() as i32);
+
+    unsafe { libc::kill(sleep_pid, libc::SIGKILL) };
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn reexec_test() {
+    use fanotify::high_level::{FanotifyBuilder, FAN_OPEN_EXEC};
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+    let dir = std::env::temp_dir().join("fanotify_reexec_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let (sh, dash, sleep) = (dir.join("sh"), dir.join("dash"), dir.join("sleep"));
+    std::fs::copy("/bin/sh", &sh).unwrap();
+    std::fs::copy("/bin/sh", &dash).unwrap();
+    std::fs::copy("/bin/sleep", &sleep).unwrap();
+    let ft = FanotifyBuilder::new()
+        .process_ancestry()
+        .nonblocking()
+        .register()
+        .unwrap();
+    // The mount of `/` holds the ELF interpreter, whose exec mustn't replace the shells.
+    ft.add_mountpoint(FAN_OPEN_EXEC, &dir).unwrap();
+    ft.add_mountpoint(FAN_OPEN_EXEC, "/").unwrap();
+
+    // The process execs `sh` and then `dash`, which starts `sleep`.
+    let mut shell = Command::new(&sh)
+        .arg("-c")
+        .arg(format!(
+            "read line; exec {} -c '{} 60 & echo $!; read line'",
+            dash.display(),
+            sleep.display()
+        ))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The events of the exec of `sh` are read first, or those of `dash` would merge with them.
+    std::thread::sleep(Duration::from_millis(100));
+    ft.read_event();
+    shell.stdin.as_mut().unwrap().write_all(b"\n").unwrap();
+    let mut line = String::new();
+    std::io::BufRead::read_line(
+        &mut std::io::BufReader::new(shell.stdout.as_mut().unwrap()),
+        &mut line,
+    )
+    .unwrap();
+    let sleep_pid: i32 = line.trim().parse().unwrap();
+    // The execs of the shell are queued before the one of `sleep`.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !ft.read_event().iter().any(|e| e.pid == sleep_pid) {
+        assert!(Instant::now() < deadline);
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    // Once the shell exited, its executable comes from the tree.
+    shell.stdin.take().unwrap().write_all(b"\n").unwrap();
+    shell.wait().unwrap();
+    let ancestors = ft.process_cache().unwrap().ancestors(sleep_pid);
+    assert_eq!(ancestors[0].pid, shell.id() as i32);
+    assert_eq!(ancestors[0].exe.as_ref(), Some(&dash));
+    assert_eq!(ancestors[1].pid, std::process::id() as i32);
+    unsafe { libc::kill(sleep_pid, libc::SIGKILL) };
+    std::fs::remove_dir_all(dir).unwrap();
+}
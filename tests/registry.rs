@@ -0,0 +1,47 @@
+#[test]
+fn registry_test() {
+    use fanotify::high_level::{
+        Fanotify, FanotifyMode, IgnoreOptions, FAN_CLOSE_WRITE, FAN_MODIFY, FAN_OPEN,
+    };
+    use fanotify::registry::MarkKind;
+    use std::path::Path;
+    let dir = "/tmp/fanotify_registry_test";
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir(dir).unwrap();
+    let ft = Fanotify::new_nonblocking(FanotifyMode::NOTIF).unwrap();
+    ft.add_path(FAN_OPEN, dir).unwrap();
+    ft.add_path(FAN_CLOSE_WRITE, dir).unwrap();
+    ft.add_mountpoint(FAN_MODIFY, dir).unwrap();
+    ft.ignore_path(FAN_MODIFY, dir, IgnoreOptions::default())
+        .unwrap();
+
+    let marks = ft.marks();
+    assert_eq!(marks.len(), 2);
+    let inode = marks.iter().find(|m| m.kind == MarkKind::Inode).unwrap();
+    assert_eq!(inode.mask, FAN_OPEN | FAN_CLOSE_WRITE);
+    assert_eq!(inode.ignored_mask, FAN_MODIFY);
+    assert_eq!(inode.path.as_deref(), Some(Path::new(dir)));
+    let mount = marks.iter().find(|m| m.kind == MarkKind::Mount).unwrap();
+    assert_eq!(mount.mask, FAN_MODIFY);
+
+    let snapshot = ft.snapshot();
+    ft.remove_path(FAN_OPEN, dir).unwrap();
+    assert_eq!(ft.marks().len(), 2);
+    assert!(ft.remove_mark(mount.id).unwrap().is_some());
+    assert!(ft.remove_mark(mount.id).unwrap().is_none());
+    assert_eq!(ft.marks().len(), 1);
+    ft.remove_mark(inode.id).unwrap();
+    assert!(ft.marks().is_empty());
+
+    let restored = Fanotify::new_nonblocking(FanotifyMode::NOTIF).unwrap();
+    restored.restore(&snapshot).unwrap();
+    let marks = restored.marks();
+    assert_eq!(marks.len(), 2);
+    for (restored, original) in marks.iter().zip(snapshot.marks()) {
+        assert_eq!(restored.kind, original.kind);
+        assert_eq!(restored.mask, original.mask);
+        assert_eq!(restored.ignored_mask, original.ignored_mask);
+        assert_eq!(restored.target, original.target);
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}